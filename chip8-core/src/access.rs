@@ -0,0 +1,54 @@
+/*
+   Record of the data memory accesses made by the last step().
+
+   Only data accesses are logged (Fx55/Fx65, 5xy2/5xy3, Fx33, Dxyn sprite reads, F002),
+   not opcode fetches. Logging is off by default and costs nothing but a flag check.
+*/
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MemoryAccess {
+    pub addr: usize,
+    pub len: usize,
+    pub kind: AccessKind,
+}
+
+impl MemoryAccess {
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.addr <= end && start < self.addr + self.len
+    }
+}
+
+#[derive(Default)]
+pub struct AccessLog {
+    enabled: bool,
+    accesses: Vec<MemoryAccess>,
+}
+
+impl AccessLog {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.accesses.clear();
+    }
+
+    pub fn clear(&mut self) {
+        if self.enabled {
+            self.accesses.clear();
+        }
+    }
+
+    pub fn record(&mut self, addr: usize, len: usize, kind: AccessKind) {
+        if self.enabled {
+            self.accesses.push(MemoryAccess { addr, len, kind });
+        }
+    }
+
+    pub fn accesses(&self) -> &[MemoryAccess] {
+        &self.accesses
+    }
+}
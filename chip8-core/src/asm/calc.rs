@@ -0,0 +1,192 @@
+use super::lexer::Token;
+use super::AsmError;
+
+/*
+   :calc expressions
+
+   Like Octo, operators have no precedence and evaluate right to left, so
+   `2 * 3 + 4` is 14. Use parentheses to group. Values are floats until they are
+   used by an instruction.
+
+   unary:  - ~ ! sin cos tan exp log abs sqrt sign ceil floor @ (byte of the program at an address)
+   binary: + - * / % & | ^ << >> pow min max < <= == != >= >
+   names:  constants, labels, PI, E, HERE
+*/
+
+pub trait Scope {
+    fn lookup(&self, name: &str) -> Option<f64>;
+    fn byte_at(&self, addr: u16) -> u8;
+    fn here(&self) -> u16;
+}
+
+const BINARY_OPS: [&str; 19] = [
+    "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", "==", "!=",
+    ">=", ">",
+];
+
+pub fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse::<i64>().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value } as f64)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    scope: &'a dyn Scope,
+    // reported for errors at the end of the expression
+    end: &'a Token,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<&'a Token, AsmError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| AsmError::at(self.end, "incomplete expression"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expression(&mut self) -> Result<f64, AsmError> {
+        let left = self.term()?;
+        let Some(op) = self.tokens.get(self.pos) else {
+            return Ok(left);
+        };
+        if op.text == ")" {
+            return Ok(left);
+        }
+        if !BINARY_OPS.contains(&op.text.as_str()) {
+            return Err(AsmError::at(op, &format!("unknown operator '{}'", op.text)));
+        }
+        self.pos += 1;
+        let right = self.expression()?;
+        let (l, r) = (left as i64, right as i64);
+        let value = match op.text.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (l & r) as f64,
+            "|" => (l | r) as f64,
+            "^" => (l ^ r) as f64,
+            "<<" | ">>" => {
+                let shifted = u32::try_from(r).ok().and_then(|r| {
+                    if op.text == "<<" {
+                        l.checked_shl(r)
+                    } else {
+                        l.checked_shr(r)
+                    }
+                });
+                let Some(shifted) = shifted else {
+                    return Err(AsmError::at(op, &format!("can't shift by {}", r)));
+                };
+                shifted as f64
+            }
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => (left < right) as i64 as f64,
+            "<=" => (left <= right) as i64 as f64,
+            "==" => (left == right) as i64 as f64,
+            "!=" => (left != right) as i64 as f64,
+            ">=" => (left >= right) as i64 as f64,
+            _ => (left > right) as i64 as f64,
+        };
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, AsmError> {
+        let token = self.next()?;
+        let text = token.text.as_str();
+        if text == "(" {
+            let value = self.expression()?;
+            let close = self.next()?;
+            if close.text != ")" {
+                return Err(AsmError::at(close, "expected ')'"));
+            }
+            return Ok(value);
+        }
+        if let Some(value) = parse_number(text) {
+            return Ok(value);
+        }
+        let unary = matches!(
+            text,
+            "-" | "~"
+                | "!"
+                | "sin"
+                | "cos"
+                | "tan"
+                | "exp"
+                | "log"
+                | "abs"
+                | "sqrt"
+                | "sign"
+                | "ceil"
+                | "floor"
+                | "@"
+        );
+        if unary {
+            let v = self.term()?;
+            let value = match text {
+                "-" => -v,
+                "~" => !(v as i64) as f64,
+                "!" => (v == 0.0) as i64 as f64,
+                "sin" => v.sin(),
+                "cos" => v.cos(),
+                "tan" => v.tan(),
+                "exp" => v.exp(),
+                "log" => v.ln(),
+                "abs" => v.abs(),
+                "sqrt" => v.sqrt(),
+                "sign" => {
+                    if v == 0.0 {
+                        0.0
+                    } else {
+                        v.signum()
+                    }
+                }
+                "ceil" => v.ceil(),
+                "floor" => v.floor(),
+                _ => self.scope.byte_at(v as u16) as f64,
+            };
+            return Ok(value);
+        }
+        match text {
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            "HERE" => Ok(self.scope.here() as f64),
+            _ => self
+                .scope
+                .lookup(text)
+                .ok_or_else(|| AsmError::at(token, &format!("undefined name '{}'", text))),
+        }
+    }
+}
+
+// Evaluate the tokens between a :calc's braces. `end` is the closing brace
+pub fn evaluate(tokens: &[Token], end: &Token, scope: &dyn Scope) -> Result<f64, AsmError> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        scope,
+        end,
+    };
+    let value = parser.expression()?;
+    if let Some(extra) = tokens.get(parser.pos) {
+        return Err(AsmError::at(extra, &format!("unexpected '{}'", extra.text)));
+    }
+    Ok(value)
+}
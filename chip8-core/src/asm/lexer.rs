@@ -0,0 +1,47 @@
+/*
+   Octo source is a flat stream of whitespace separated tokens. '#' starts a comment
+   that runs to the end of the line. Every token keeps its 1-based line/column so
+   errors can point back into the source.
+*/
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    for (line_ind, line) in source.lines().enumerate() {
+        let mut current = String::new();
+        let mut start = 0;
+        for (col_ind, c) in line.chars().enumerate() {
+            if c == '#' {
+                break;
+            }
+            if c.is_whitespace() {
+                if !current.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut current),
+                        line: line_ind + 1,
+                        column: start + 1,
+                    });
+                }
+                continue;
+            }
+            if current.is_empty() {
+                start = col_ind;
+            }
+            current.push(c);
+        }
+        if !current.is_empty() {
+            tokens.push(Token {
+                text: current,
+                line: line_ind + 1,
+                column: start + 1,
+            });
+        }
+    }
+    tokens
+}
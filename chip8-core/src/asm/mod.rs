@@ -0,0 +1,943 @@
+use calc::Scope;
+use lexer::Token;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+mod calc;
+mod lexer;
+
+/*
+   Assembler for Octo (.8o) source.
+
+   Supported: labels (`: name`), `:alias`, `:const`, `:calc`, `:byte`, `:org`, `:macro`,
+   `:call`, `:unpack`, `loop`/`while`/`again`, `if ... then` and `if ... begin/else/end`,
+   all CHIP-8 and SUPER-CHIP statements and the XO-CHIP `long`, `plane`, `audio`,
+   `scroll-up`, `pitch` and `save/load vx - vy` forms. `:breakpoint` and `:monitor` are
+   accepted and ignored.
+
+   Like Octo, the program starts at 0x200 and execution begins at the `main` label. If
+   `main` isn't the first thing in the program a `jump main` is placed at 0x200.
+   Labels may be used before they're defined wherever an address is expected.
+*/
+
+pub const PROGRAM_START: u16 = 0x200;
+// Macros can invoke themselves, stop before that eats all memory
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Clone, Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn at(token: &Token, message: &str) -> Self {
+        Self {
+            line: token.line,
+            column: token.column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+pub struct Program {
+    // to be loaded at PROGRAM_START
+    pub bytes: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let mut asm = Assembler::new(lexer::tokenize(source));
+    while !asm.tokens.is_empty() {
+        asm.statement()?;
+    }
+    asm.finish()
+}
+
+#[derive(Copy, Clone)]
+enum FixupKind {
+    // low 12 bits of the opcode at addr
+    Nnn,
+    // 16 bit word at addr
+    Long,
+    // unpack halves of an address into a byte
+    HighByte,
+    LowByte,
+}
+
+struct Fixup {
+    addr: u32,
+    kind: FixupKind,
+    name: Token,
+}
+
+#[derive(Clone)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+// A parsed `vx <op> <rhs>` test, emitted once we know which way the skip should go
+struct Condition {
+    x: u16,
+    op: Token,
+    rhs: Option<Token>,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    last: Token,
+    rom: Vec<u8>,
+    here: u32,
+    started: bool,
+    jump_to_main: bool,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u16>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    // loop start and the exit jumps of its whiles
+    loops: Vec<(Token, u32, Vec<u32>)>,
+    // jumps of open if/begin and else blocks, patched at the matching else/end
+    branches: Vec<(Token, u32)>,
+    expansions: usize,
+}
+
+impl Scope for Assembler {
+    fn lookup(&self, name: &str) -> Option<f64> {
+        match self.constants.get(name) {
+            Some(v) => Some(*v),
+            None => self.labels.get(name).map(|a| *a as f64),
+        }
+    }
+
+    fn byte_at(&self, addr: u16) -> u8 {
+        match addr.checked_sub(PROGRAM_START) {
+            Some(offset) => self.rom.get(offset as usize).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn here(&self) -> u16 {
+        self.here as u16
+    }
+}
+
+impl Assembler {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens: tokens.into(),
+            last: Token {
+                text: String::new(),
+                line: 1,
+                column: 1,
+            },
+            rom: vec![],
+            here: PROGRAM_START as u32,
+            started: false,
+            jump_to_main: false,
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: vec![],
+            loops: vec![],
+            branches: vec![],
+            expansions: 0,
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.last = token.clone();
+                Ok(token)
+            }
+            None => Err(AsmError::at(&self.last, "unexpected end of file")),
+        }
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        matches!(self.tokens.front(), Some(t) if t.text == text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(AsmError::at(
+                &token,
+                &format!("expected '{}', got '{}'", text, token.text),
+            ));
+        }
+        Ok(token)
+    }
+
+    // The first code decides whether 0x200 needs a `jump main`
+    fn start(&mut self, is_main: bool) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        if !is_main {
+            self.jump_to_main = true;
+            self.rom.extend_from_slice(&[0, 0]);
+            self.here += 2;
+        }
+    }
+
+    fn write_byte(&mut self, addr: u32, byte: u8) {
+        let offset = (addr - PROGRAM_START as u32) as usize;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+    }
+
+    fn emit_byte(&mut self, token: &Token, byte: u8) -> Result<(), AsmError> {
+        if self.here > 0xFFFF {
+            return Err(AsmError::at(token, "program is larger than 64K"));
+        }
+        self.write_byte(self.here, byte);
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit(&mut self, token: &Token, word: u16) -> Result<(), AsmError> {
+        self.emit_byte(token, (word >> 8) as u8)?;
+        self.emit_byte(token, word as u8)
+    }
+
+    fn patch_jump(&mut self, token: &Token, addr: u32, target: u32) -> Result<(), AsmError> {
+        if target > 0xFFF {
+            return Err(AsmError::at(
+                token,
+                &format!("jump target 0x{:X} is out of range", target),
+            ));
+        }
+        let word = 0x1000 | target as u16;
+        self.write_byte(addr, (word >> 8) as u8);
+        self.write_byte(addr + 1, word as u8);
+        Ok(())
+    }
+
+    fn register(&self, token: &Token) -> Option<u16> {
+        if let Some(x) = self.aliases.get(&token.text) {
+            return Some(*x);
+        }
+        let text = token.text.to_lowercase();
+        let digit = text.strip_prefix('v')?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.register(&token).ok_or_else(|| {
+            AsmError::at(
+                &token,
+                &format!("expected a register, got '{}'", token.text),
+            )
+        })
+    }
+
+    fn check_name(&self, token: &Token) -> Result<(), AsmError> {
+        if calc::parse_number(&token.text).is_some()
+            || self.register(token).is_some()
+            || token.text.starts_with(':')
+            || token.text == "{"
+            || token.text == "}"
+        {
+            return Err(AsmError::at(
+                token,
+                &format!("'{}' can't be used as a name", token.text),
+            ));
+        }
+        Ok(())
+    }
+
+    // Tokens up to the '}' matching an already consumed '{', and the '}' itself
+    fn block(&mut self) -> Result<(Vec<Token>, Token), AsmError> {
+        let mut depth = 0;
+        let mut body = vec![];
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok((body, token)),
+                "}" => depth -= 1,
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn calc(&mut self) -> Result<f64, AsmError> {
+        let (body, end) = self.block()?;
+        calc::evaluate(&body, &end, self)
+    }
+
+    // A number, constant, label or `{ expression }`, which must already be defined
+    fn value(&mut self, token: &Token) -> Result<i64, AsmError> {
+        if token.text == "{" {
+            return Ok(self.calc()? as i64);
+        }
+        let value = match calc::parse_number(&token.text) {
+            Some(v) => Some(v),
+            None => self.lookup(&token.text),
+        };
+        value
+            .map(|v| v as i64)
+            .ok_or_else(|| AsmError::at(token, &format!("undefined name '{}'", token.text)))
+    }
+
+    fn ranged(&mut self, token: &Token, min: i64, max: i64) -> Result<i64, AsmError> {
+        let value = self.value(token)?;
+        if value < min || value > max {
+            return Err(AsmError::at(
+                token,
+                &format!("value {} is out of range ({} to {})", value, min, max),
+            ));
+        }
+        Ok(value)
+    }
+
+    fn byte(&mut self, token: &Token) -> Result<u16, AsmError> {
+        Ok((self.ranged(token, -128, 255)? & 0xFF) as u16)
+    }
+
+    fn nibble(&mut self, token: &Token) -> Result<u16, AsmError> {
+        Ok(self.ranged(token, 0, 15)? as u16)
+    }
+
+    fn fixup_range(kind: FixupKind, name: &Token, value: i64) -> Result<(), AsmError> {
+        let max = match kind {
+            FixupKind::Nnn => 0xFFF,
+            _ => 0xFFFF,
+        };
+        if value < 0 || value > max {
+            return Err(AsmError::at(
+                name,
+                &format!("address 0x{:X} of '{}' is out of range", value, name.text),
+            ));
+        }
+        Ok(())
+    }
+
+    // An address that may be a label defined further down. Unknown names are patched in finish()
+    fn address(&mut self, token: &Token, fixups: &[(FixupKind, u32)]) -> Result<u16, AsmError> {
+        let known = token.text == "{"
+            || calc::parse_number(&token.text).is_some()
+            || self.lookup(&token.text).is_some();
+        if known {
+            let value = self.value(token)?;
+            for (kind, _) in fixups {
+                Self::fixup_range(*kind, token, value)?;
+            }
+            return Ok(value as u16);
+        }
+        self.check_name(token)?;
+        for (kind, addr) in fixups {
+            self.fixups.push(Fixup {
+                addr: *addr,
+                kind: *kind,
+                name: token.clone(),
+            });
+        }
+        Ok(0)
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        let text = token.text.as_str();
+
+        let emits_code = !matches!(
+            text,
+            ":" | ":alias" | ":const" | ":calc" | ":macro" | ":breakpoint" | ":monitor"
+        ) && !self.macros.contains_key(text);
+        if emits_code {
+            self.start(false);
+        }
+
+        match text {
+            ":" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                if self.labels.contains_key(&name.text) {
+                    return Err(AsmError::at(
+                        &name,
+                        &format!("label '{}' is already defined", name.text),
+                    ));
+                }
+                self.start(name.text == "main");
+                self.labels.insert(name.text, self.here as u16);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let x = self.expect_register()?;
+                self.aliases.insert(name.text, x);
+            }
+            ":const" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let value_token = self.next()?;
+                let value = self.value(&value_token)?;
+                self.constants.insert(name.text, value as f64);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name.text, value);
+            }
+            ":byte" => {
+                let value_token = self.next()?;
+                let byte = self.byte(&value_token)?;
+                self.emit_byte(&token, byte as u8)?;
+            }
+            ":org" => {
+                let value_token = self.next()?;
+                let addr = self.ranged(&value_token, PROGRAM_START as i64, 0xFFFF)?;
+                self.here = addr as u32;
+            }
+            ":macro" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let mut args = vec![];
+                loop {
+                    let arg = self.next()?;
+                    if arg.text == "{" {
+                        break;
+                    }
+                    args.push(arg.text);
+                }
+                let (body, _) = self.block()?;
+                self.macros.insert(name.text, Macro { args, body });
+            }
+            ":call" => {
+                let target = self.next()?;
+                let nnn = self.address(&target, &[(FixupKind::Nnn, self.here)])?;
+                self.emit(&token, 0x2000 | nnn)?;
+            }
+            ":unpack" => {
+                let first = self.next()?;
+                let (high, kind) = match first.text.as_str() {
+                    "long" => (0, FixupKind::Long),
+                    _ => (self.nibble(&first)? << 4, FixupKind::Nnn),
+                };
+                let target = self.next()?;
+                let fixups = [
+                    (FixupKind::HighByte, self.here + 1),
+                    (FixupKind::LowByte, self.here + 3),
+                ];
+                let addr = self.address(&target, &fixups)?;
+                if self.lookup(&target.text).is_some() {
+                    Self::fixup_range(kind, &target, addr as i64)?;
+                }
+                self.emit(&token, 0x6000 | high | (addr >> 8))?;
+                self.emit(&token, 0x6100 | (addr & 0xFF))?;
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "return" | ";" => self.emit(&token, 0x00EE)?,
+            "clear" => self.emit(&token, 0x00E0)?,
+            "hires" => self.emit(&token, 0x00FF)?,
+            "lores" => self.emit(&token, 0x00FE)?,
+            "exit" => self.emit(&token, 0x00FD)?,
+            "scroll-left" => self.emit(&token, 0x00FC)?,
+            "scroll-right" => self.emit(&token, 0x00FB)?,
+            "audio" => self.emit(&token, 0xF002)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let n_token = self.next()?;
+                let n = self.nibble(&n_token)?;
+                let word = match text {
+                    "scroll-down" => 0x00C0 | n,
+                    "scroll-up" => 0x00D0 | n,
+                    _ => 0xF001 | n << 8,
+                };
+                self.emit(&token, word)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.expect_register()?;
+                let low = match text {
+                    "bcd" => 0x33,
+                    "saveflags" => 0x75,
+                    _ => 0x85,
+                };
+                self.emit(&token, 0xF000 | x << 8 | low)?;
+            }
+            "save" | "load" => {
+                let x = self.expect_register()?;
+                let save = text == "save";
+                if self.peek_is("-") {
+                    self.next()?;
+                    let y = self.expect_register()?;
+                    let n = if save { 0x2 } else { 0x3 };
+                    self.emit(&token, 0x5000 | x << 8 | y << 4 | n)?;
+                } else {
+                    let low = if save { 0x55 } else { 0x65 };
+                    self.emit(&token, 0xF000 | x << 8 | low)?;
+                }
+            }
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let n_token = self.next()?;
+                let n = self.nibble(&n_token)?;
+                self.emit(&token, 0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.next()?;
+                let nnn = self.address(&target, &[(FixupKind::Nnn, self.here)])?;
+                let high = match text {
+                    "jump" => 0x1000,
+                    "jump0" => 0xB000,
+                    _ => 0x0000,
+                };
+                self.emit(&token, high | nnn)?;
+            }
+            "loop" => self.loops.push((token.clone(), self.here, vec![])),
+            "while" => {
+                let condition = self.condition()?;
+                if self.loops.is_empty() {
+                    return Err(AsmError::at(&token, "'while' outside of a loop"));
+                }
+                self.emit_condition(&condition, true)?;
+                let exit = self.here;
+                if let Some((_, _, exits)) = self.loops.last_mut() {
+                    exits.push(exit);
+                }
+                self.emit(&token, 0x1000)?;
+            }
+            "again" => {
+                let Some((_, start, exits)) = self.loops.pop() else {
+                    return Err(AsmError::at(&token, "'again' without 'loop'"));
+                };
+                let here = self.here;
+                self.emit(&token, 0)?;
+                self.patch_jump(&token, here, start)?;
+                for exit in exits {
+                    self.patch_jump(&token, exit, self.here)?;
+                }
+            }
+            "if" => {
+                let condition = self.condition()?;
+                let block = self.next()?;
+                match block.text.as_str() {
+                    "then" => self.emit_condition(&condition, false)?,
+                    "begin" => {
+                        self.emit_condition(&condition, true)?;
+                        self.branches.push((block, self.here));
+                        self.emit(&token, 0x1000)?;
+                    }
+                    _ => {
+                        return Err(AsmError::at(
+                            &block,
+                            &format!("expected 'then' or 'begin', got '{}'", block.text),
+                        ))
+                    }
+                }
+            }
+            "else" => {
+                let Some((_, jump)) = self.branches.pop() else {
+                    return Err(AsmError::at(&token, "'else' without 'if ... begin'"));
+                };
+                let else_jump = self.here;
+                self.emit(&token, 0x1000)?;
+                self.patch_jump(&token, jump, self.here)?;
+                self.branches.push((token.clone(), else_jump));
+            }
+            "end" => {
+                let Some((_, jump)) = self.branches.pop() else {
+                    return Err(AsmError::at(&token, "'end' without 'if ... begin'"));
+                };
+                self.patch_jump(&token, jump, self.here)?;
+            }
+            "i" => {
+                let op = self.next()?;
+                match op.text.as_str() {
+                    ":=" => {
+                        let rhs = self.next()?;
+                        match rhs.text.as_str() {
+                            "long" => {
+                                let target = self.next()?;
+                                let addr =
+                                    self.address(&target, &[(FixupKind::Long, self.here + 2)])?;
+                                self.emit(&token, 0xF000)?;
+                                self.emit(&token, addr)?;
+                            }
+                            "hex" | "bighex" => {
+                                let x = self.expect_register()?;
+                                let low = if rhs.text == "hex" { 0x29 } else { 0x30 };
+                                self.emit(&token, 0xF000 | x << 8 | low)?;
+                            }
+                            _ => {
+                                let nnn = self.address(&rhs, &[(FixupKind::Nnn, self.here)])?;
+                                self.emit(&token, 0xA000 | nnn)?;
+                            }
+                        }
+                    }
+                    "+=" => {
+                        let x = self.expect_register()?;
+                        self.emit(&token, 0xF01E | x << 8)?;
+                    }
+                    _ => {
+                        return Err(AsmError::at(
+                            &op,
+                            &format!("expected ':=' or '+=', got '{}'", op.text),
+                        ))
+                    }
+                }
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.expect_register()?;
+                let low = match text {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit(&token, 0xF000 | x << 8 | low)?;
+            }
+            _ => {
+                if let Some(x) = self.register(&token) {
+                    return self.assignment(&token, x);
+                }
+                if let Some(m) = self.macros.get(text).cloned() {
+                    return self.expand(&token, m);
+                }
+                if text.starts_with(':') {
+                    return Err(AsmError::at(
+                        &token,
+                        &format!("unknown directive '{}'", text),
+                    ));
+                }
+                if calc::parse_number(text).is_some() || self.constants.contains_key(text) {
+                    // bare numbers are data
+                    let byte = self.byte(&token)?;
+                    return self.emit_byte(&token, byte as u8);
+                }
+                // anything else is a call to a (possibly later) label
+                let nnn = self.address(&token, &[(FixupKind::Nnn, self.here)])?;
+                self.emit(&token, 0x2000 | nnn)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, token: &Token, x: u16) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let rhs = self.next()?;
+        let x = x << 8;
+        let word = match (op.text.as_str(), self.register(&rhs)) {
+            (":=", Some(y)) => 0x8000 | x | y << 4,
+            (":=", None) => match rhs.text.as_str() {
+                "random" => {
+                    let mask = self.next()?;
+                    0xC000 | x | self.byte(&mask)?
+                }
+                "delay" => 0xF007 | x,
+                "key" => 0xF00A | x,
+                _ => 0x6000 | x | self.byte(&rhs)?,
+            },
+            ("+=", Some(y)) => 0x8004 | x | y << 4,
+            ("+=", None) => 0x7000 | x | self.byte(&rhs)?,
+            ("-=", Some(y)) => 0x8005 | x | y << 4,
+            ("-=", None) => 0x7000 | x | (self.byte(&rhs)? as u8).wrapping_neg() as u16,
+            ("=-", Some(y)) => 0x8007 | x | y << 4,
+            ("|=", Some(y)) => 0x8001 | x | y << 4,
+            ("&=", Some(y)) => 0x8002 | x | y << 4,
+            ("^=", Some(y)) => 0x8003 | x | y << 4,
+            (">>=", Some(y)) => 0x8006 | x | y << 4,
+            ("<<=", Some(y)) => 0x800E | x | y << 4,
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(AsmError::at(
+                    &rhs,
+                    &format!("expected a register, got '{}'", rhs.text),
+                ))
+            }
+            _ => {
+                return Err(AsmError::at(
+                    &op,
+                    &format!("unknown operator '{}'", op.text),
+                ))
+            }
+        };
+        self.emit(token, word)
+    }
+
+    fn expand(&mut self, token: &Token, m: Macro) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(AsmError::at(token, "too many macro expansions"));
+        }
+        let mut args = HashMap::new();
+        for name in m.args.iter() {
+            args.insert(name.clone(), self.next()?);
+        }
+        for body_token in m.body.iter().rev() {
+            let expanded = match args.get(&body_token.text) {
+                Some(arg) => arg.clone(),
+                None => body_token.clone(),
+            };
+            self.tokens.push_front(expanded);
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.expect_register()?;
+        let op = self.next()?;
+        let rhs = match op.text.as_str() {
+            "key" | "-key" => None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => Some(self.next()?),
+            _ => {
+                return Err(AsmError::at(
+                    &op,
+                    &format!("expected a comparison, got '{}'", op.text),
+                ))
+            }
+        };
+        Ok(Condition { x, op, rhs })
+    }
+
+    /*
+       Emit a test that skips the next instruction when the condition evaluates to
+       `skip_on`. The ordering comparisons have no opcode of their own and go through VF:
+       for < and >= VF ends up as (vx >= rhs), for > and <= as (rhs >= vx)
+    */
+    fn emit_condition(&mut self, condition: &Condition, skip_on: bool) -> Result<(), AsmError> {
+        let x = condition.x;
+        let op = &condition.op;
+        let Some(rhs) = &condition.rhs else {
+            let skip_pressed = (op.text == "key") == skip_on;
+            let low = if skip_pressed { 0x9E } else { 0xA1 };
+            return self.emit(op, 0xE000 | x << 8 | low);
+        };
+        let reg = self.register(rhs);
+        match op.text.as_str() {
+            "==" | "!=" => {
+                let skip_equal = (op.text == "==") == skip_on;
+                let word = match reg {
+                    Some(y) if skip_equal => 0x5000 | x << 8 | y << 4,
+                    Some(y) => 0x9000 | x << 8 | y << 4,
+                    None if skip_equal => 0x3000 | x << 8 | self.byte(rhs)?,
+                    None => 0x4000 | x << 8 | self.byte(rhs)?,
+                };
+                self.emit(op, word)
+            }
+            _ => {
+                let swapped = op.text == ">" || op.text == "<=";
+                match (reg, swapped) {
+                    (Some(y), false) => {
+                        self.emit(op, 0x8F00 | x << 4)?;
+                        self.emit(op, 0x8F05 | y << 4)?;
+                    }
+                    (Some(y), true) => {
+                        self.emit(op, 0x8F00 | y << 4)?;
+                        self.emit(op, 0x8F05 | x << 4)?;
+                    }
+                    (None, false) => {
+                        let kk = self.byte(rhs)?;
+                        self.emit(op, 0x6F00 | kk)?;
+                        self.emit(op, 0x8F07 | x << 4)?;
+                    }
+                    (None, true) => {
+                        let kk = self.byte(rhs)?;
+                        self.emit(op, 0x6F00 | kk)?;
+                        self.emit(op, 0x8F05 | x << 4)?;
+                    }
+                }
+                let holds = if op.text == ">=" || op.text == "<=" {
+                    1
+                } else {
+                    0
+                };
+                let skip = if skip_on { 0x3F00 } else { 0x4F00 };
+                self.emit(op, skip | holds)
+            }
+        }
+    }
+
+    fn finish(mut self) -> Result<Program, AsmError> {
+        if let Some((token, _, _)) = self.loops.first() {
+            return Err(AsmError::at(token, "'loop' without 'again'"));
+        }
+        if let Some((token, _)) = self.branches.first() {
+            return Err(AsmError::at(token, "'begin' without 'end'"));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let value = self.lookup(&fixup.name.text).ok_or_else(|| {
+                AsmError::at(
+                    &fixup.name,
+                    &format!("undefined name '{}'", fixup.name.text),
+                )
+            })? as i64;
+            Self::fixup_range(fixup.kind, &fixup.name, value)?;
+            let value = value as u16;
+            let offset = (fixup.addr - PROGRAM_START as u32) as usize;
+            match fixup.kind {
+                FixupKind::Nnn => {
+                    self.rom[offset] = (self.rom[offset] & 0xF0) | (value >> 8) as u8;
+                    self.rom[offset + 1] = value as u8;
+                }
+                FixupKind::Long => {
+                    self.rom[offset] = (value >> 8) as u8;
+                    self.rom[offset + 1] = value as u8;
+                }
+                FixupKind::HighByte => self.rom[offset] |= (value >> 8) as u8,
+                FixupKind::LowByte => self.rom[offset] = value as u8,
+            }
+        }
+
+        if self.jump_to_main {
+            let Some(main) = self.labels.get("main").copied() else {
+                let start = Token {
+                    text: String::new(),
+                    line: 1,
+                    column: 1,
+                };
+                return Err(AsmError::at(&start, "program has no 'main' label"));
+            };
+            let token = self.last.clone();
+            self.patch_jump(&token, PROGRAM_START as u32, main as u32)?;
+        }
+
+        Ok(Program {
+            bytes: self.rom,
+            labels: self.labels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        match assemble(source) {
+            Ok(program) => program.bytes,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = lexer::tokenize("v0 := 1 # comment := 2\n\t: main");
+        let found: Vec<_> = tokens
+            .iter()
+            .map(|t| (t.text.as_str(), t.line, t.column))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("v0", 1, 1),
+                (":=", 1, 4),
+                ("1", 1, 7),
+                (":", 2, 2),
+                ("main", 2, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn jump_to_main() {
+        let source = "
+            : data 0xAB
+            : main
+                i := data
+                jump main
+        ";
+        assert_eq!(bytes(source), [0x12, 0x03, 0xAB, 0xA2, 0x02, 0x12, 0x03]);
+    }
+
+    #[test]
+    fn calc() {
+        let source = "
+            : main
+            :calc right-to-left { 2 * 3 + 4 }
+            :calc grouped { ( 2 * 3 ) + 4 }
+            :calc shifted { 1 << 4 >> 1 }
+            v0 := right-to-left
+            :calc at { @ 0x200 }
+            v1 := grouped
+            v2 := shifted
+            v3 := at
+        ";
+        assert_eq!(bytes(source), [0x60, 14, 0x61, 10, 0x62, 4, 0x63, 0x60]);
+
+        for shift in ["1 << 64", "1 >> 64", "1 << -1", "1 >> -1"] {
+            let source = format!(": main\n:calc x {{ {} }}", shift);
+            let err = assemble(&source).err().expect(shift);
+            assert_eq!((err.line, err.column), (2, 13), "{}", err);
+        }
+    }
+
+    #[test]
+    fn macros() {
+        let source = "
+            :macro set reg val { reg := val }
+            :macro twice reg { set reg 1 set reg 2 }
+            : main
+                set v3 7
+                twice v4
+        ";
+        assert_eq!(bytes(source), [0x63, 0x07, 0x64, 0x01, 0x64, 0x02]);
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = "
+            : main
+                loop
+                    v0 += 1
+                    while v0 != 5
+                again
+                if v1 == 2 then v2 := 3
+                if v1 > 4 begin
+                    v3 := 1
+                else
+                    v3 := 2
+                end
+        ";
+        #[rustfmt::skip]
+        let expected = [
+            0x70, 0x01, // 200: v0 += 1
+            0x40, 0x05, // 202: skip the exit while v0 != 5
+            0x12, 0x08, // 204: exit
+            0x12, 0x00, // 206: again
+            0x41, 0x02, // 208: skip unless v1 == 2
+            0x62, 0x03, // 20A: v2 := 3
+            0x6F, 0x04, // 20C: vf := 4
+            0x8F, 0x15, // 20E: vf -= v1
+            0x3F, 0x00, // 210: skip when v1 > 4
+            0x12, 0x18, // 212: to else
+            0x63, 0x01, // 214
+            0x12, 0x1A, // 216: to end
+            0x63, 0x02, // 218
+        ];
+        assert_eq!(bytes(source), expected);
+    }
+}
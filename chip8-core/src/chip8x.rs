@@ -0,0 +1,113 @@
+/*
+   CHIP-8X hardware: the VP-590 colour board and VP-595 tone generator.
+
+   Pixels stay on/off in the screen layers. The colour board adds a background colour
+   for the whole display (stepped by 02A0) and a foreground colour for each 8x1 zone of
+   the 64x32 display, ie. one per sprite byte. Bxy0 colours blocks of 8x4 zones and Bxyn
+   n rows of a single column of zones.
+
+   Colours are indices, see the palettes below. Frontends map a lit pixel to the
+   foreground of its zone and every other pixel to the background.
+*/
+
+// Zones across and down the 64x32 display
+pub const ZONE_COLS: usize = 8;
+pub const ZONE_ROWS: usize = 32;
+// Lores pixels per zone
+pub const ZONE_WIDTH: usize = 8;
+// Rows per zone of Bxy0
+const BLOCK_HEIGHT: usize = 4;
+
+// 02A0 steps through these: dark blue, black, green, red
+pub const BACKGROUND_COLORS: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+// 3 bit RGB as the VP-590 wires it: black, red, blue, violet, green, yellow, aqua, white
+pub const FOREGROUND_COLORS: [u32; 8] = [
+    0x000000, 0xFF0000, 0x0000FF, 0xFF00FF, 0x00FF00, 0xFFFF00, 0x00FFFF, 0xFFFFFF,
+];
+
+// The interpreter starts with red on dark blue
+const DEFAULT_FOREGROUND: u8 = 1;
+
+// VP-595 clock divided by the FxF8 value + 1 gives the tone frequency in Hz
+const TONE_CLOCK: f32 = 27535.0;
+// The value the interpreter writes to the VP-595 on reset
+pub const DEFAULT_TONE: u8 = 0x80;
+// Square wave for the XO-CHIP audio pattern buffer, 16 bits per period
+pub const TONE_PATTERN: [u8; 16] = [
+    0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+    0x00,
+];
+
+#[derive(Clone, Debug)]
+pub struct ColorBoard {
+    pub background: u8,
+    // foreground index per zone, row by row
+    pub zones: Vec<u8>,
+}
+
+impl Default for ColorBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ColorBoard {
+    pub fn new() -> Self {
+        Self {
+            background: 0,
+            zones: vec![DEFAULT_FOREGROUND; ZONE_COLS * ZONE_ROWS],
+        }
+    }
+
+    // 02A0
+    pub fn step_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS.len() as u8;
+    }
+
+    /*
+       Bxy0: the low nibbles of `h` and `v` are the first column and block row, the high
+       nibbles how many more to colour. Blocks past the edge are left alone
+    */
+    pub fn fill_blocks(&mut self, h: u8, v: u8, color: u8) {
+        let cols = (h & 0xF) as usize..=((h & 0xF) + (h >> 4)) as usize;
+        let blocks = (v & 0xF) as usize..=((v & 0xF) + (v >> 4)) as usize;
+        for block in blocks {
+            for row in block * BLOCK_HEIGHT..(block + 1) * BLOCK_HEIGHT {
+                for col in cols.clone() {
+                    self.set(col, row, color);
+                }
+            }
+        }
+    }
+
+    // Bxyn: `n` rows of the column holding pixel `x`, from pixel row `y`
+    pub fn fill_rows(&mut self, x: u8, y: u8, n: u8, color: u8) {
+        let col = x as usize % 64 / ZONE_WIDTH;
+        for row in y as usize..y as usize + n as usize {
+            self.set(col, row, color);
+        }
+    }
+
+    fn set(&mut self, col: usize, row: usize, color: u8) {
+        if col < ZONE_COLS && row < ZONE_ROWS {
+            self.zones[row * ZONE_COLS + col] = color & 0x7;
+        }
+    }
+
+    // Foreground index of the lores pixel at x, y
+    pub fn foreground_at(&self, x: usize, y: usize) -> u8 {
+        self.zones[(y % ZONE_ROWS) * ZONE_COLS + (x / ZONE_WIDTH) % ZONE_COLS]
+    }
+}
+
+// 5xy1: each nibble is added on its own, modulo 8
+pub fn add_nibbles(a: u8, b: u8) -> u8 {
+    ((a & 0x77) + (b & 0x77)) & 0x77
+}
+
+// FxF8 value as the XO-CHIP pitch that plays TONE_PATTERN at the VP-595 frequency
+pub fn tone_pitch(value: u8) -> u8 {
+    let hz = TONE_CLOCK / (value as f32 + 1.0);
+    let rate = hz * 16.0;
+    (64.0 + 48.0 * (rate / 4000.0).log2()).round().clamp(0.0, 255.0) as u8
+}
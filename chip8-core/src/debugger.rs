@@ -0,0 +1,429 @@
+use crate::access::MemoryAccess;
+use crate::error::CoreError;
+use crate::watch::{Condition, Watchpoint};
+use crate::{Chip8, StepResult};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/*
+   Execution control around Chip8::step()
+
+   The debugger owns no machine state, it only decides whether the next instruction
+   should run. Frontends call run() in place of their step() loop and get back the
+   reason execution stopped, if it did.
+
+   Breakpoints may carry a condition and only stop when it holds. Watchpoints stop after
+   an instruction that touched a watched address range, and global conditions stop after
+   the instruction that made them become true.
+*/
+
+#[derive(Debug)]
+pub enum StopReason {
+    Breakpoint(u16),
+    Step(u16),
+    // pc and opcode of the instruction that triggered the stop
+    Watchpoint {
+        pc: u16,
+        opcode: u16,
+        access: MemoryAccess,
+    },
+    Condition {
+        pc: u16,
+        opcode: u16,
+        condition: String,
+    },
+    Error(CoreError),
+    // 00FD at pc, the machine stays halted until it is reset
+    Exited(u16),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint(pc) => write!(f, "Breakpoint at 0x{:04X}", pc),
+            StopReason::Step(pc) => write!(f, "Stopped at 0x{:04X}", pc),
+            StopReason::Watchpoint { pc, opcode, access } => write!(
+                f,
+                "{:?} 0x{:04X}-0x{:04X} by {:04X} at 0x{:04X}",
+                access.kind,
+                access.addr,
+                access.addr + access.len - 1,
+                opcode,
+                pc
+            ),
+            StopReason::Condition {
+                pc,
+                opcode,
+                condition,
+            } => write!(f, "{} after {:04X} at 0x{:04X}", condition, opcode, pc),
+            StopReason::Error(e) => {
+                write!(f, "{}", e.error_type.to_string().replace('\n', " - "))
+            }
+            StopReason::Exited(pc) => write!(f, "Interpreter exited at 0x{:04X}", pc),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum StepMode {
+    Continue,
+    Into,
+    // Stop once we're back at `return_pc` with the stack at `sp` (ie. the 2nnn returned)
+    Over { return_pc: u16, sp: u16 },
+    // Stop once the current subroutine has returned
+    Out { sp: u16 },
+    RunTo(u16),
+}
+
+pub struct Debugger {
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    // conditions with their value after the last instruction, they trigger on false -> true
+    conditions: Vec<(Condition, bool)>,
+    paused: bool,
+    mode: StepMode,
+    // Set when resuming from a breakpoint so we don't immediately stop on it again
+    skip_breakpoint: bool,
+    // cost the last run() went over its budget by, taken from the next one
+    overrun: u32,
+    pub break_on_error: bool,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeMap::new(),
+            watchpoints: vec![],
+            conditions: vec![],
+            paused: false,
+            mode: StepMode::Continue,
+            skip_breakpoint: false,
+            overrun: 0,
+            break_on_error: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &u16> {
+        self.breakpoints.keys()
+    }
+
+    pub fn has_breakpoint(&self, addr: u16) -> bool {
+        self.breakpoints.contains_key(&addr)
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: u16) {
+        if self.breakpoints.remove(&addr).is_none() {
+            self.breakpoints.insert(addr, None);
+        }
+    }
+
+    pub fn set_breakpoint(&mut self, addr: u16, condition: Option<Condition>) {
+        self.breakpoints.insert(addr, condition);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn breakpoint_condition(&self, addr: u16) -> Option<&Condition> {
+        self.breakpoints.get(&addr).and_then(|c| c.as_ref())
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn conditions(&self) -> impl Iterator<Item = &Condition> {
+        self.conditions.iter().map(|(c, _)| c)
+    }
+
+    pub fn add_condition(&mut self, condition: Condition) {
+        self.conditions.push((condition, false));
+    }
+
+    pub fn clear_conditions(&mut self) {
+        self.conditions.clear();
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.mode = StepMode::Continue;
+    }
+
+    pub fn resume(&mut self) {
+        self.start(StepMode::Continue);
+    }
+
+    pub fn step_into(&mut self) {
+        self.start(StepMode::Into);
+    }
+
+    // Runs a whole 2nnn subroutine call as one step. Anything else is a plain step_into()
+    pub fn step_over(&mut self, chip: &Chip8) {
+        let pc = chip.pc();
+        match chip.opcode_at(pc) {
+            Some(opcode) if opcode & 0xF000 == 0x2000 => self.start(StepMode::Over {
+                return_pc: pc.wrapping_add(2),
+                sp: chip.sp(),
+            }),
+            _ => self.step_into(),
+        }
+    }
+
+    // Runs until the current subroutine returns (00EE)
+    pub fn step_out(&mut self, chip: &Chip8) {
+        if chip.sp() == 0 {
+            // not in a subroutine, nothing to step out of
+            self.step_into();
+        } else {
+            self.start(StepMode::Out { sp: chip.sp() });
+        }
+    }
+
+    pub fn run_to(&mut self, addr: u16) {
+        self.start(StepMode::RunTo(addr));
+    }
+
+    fn start(&mut self, mode: StepMode) {
+        self.mode = mode;
+        self.skip_breakpoint = self.paused;
+        self.paused = false;
+    }
+
+    fn stop(&mut self, reason: StopReason) -> Option<StopReason> {
+        self.paused = true;
+        self.mode = StepMode::Continue;
+        Some(reason)
+    }
+
+    /*
+       Execute instructions until their costs (see Timing) add up to `budget`, less what
+       the previous call overran by. Returns early (without an error) when the machine
+       blocks on input/display wait, or with Some(reason) when execution stops (including
+       Exited, which doesn't pause). CoreErrors are returned as Err unless break_on_error
+       is set
+    */
+    pub fn run(
+        &mut self,
+        chip: &mut Chip8,
+        budget: u32,
+    ) -> Result<Option<StopReason>, CoreError> {
+        chip.set_access_tracking(!self.watchpoints.is_empty());
+        let mut spent = self.overrun;
+        self.overrun = 0;
+        while spent < budget {
+            if self.paused {
+                return Ok(None);
+            }
+            if chip.has_exited() {
+                // Not a pause, there's nothing to resume
+                return Ok(Some(StopReason::Exited(chip.pc())));
+            }
+            if chip.is_blocked() {
+                // Fx0A or display wait - nothing will happen until the next frame
+                return Ok(None);
+            }
+
+            let pc = chip.pc();
+            if !self.skip_breakpoint {
+                let hit = match self.breakpoints.get(&pc) {
+                    Some(Some(condition)) => condition.eval(chip),
+                    Some(None) => true,
+                    None => false,
+                };
+                if hit {
+                    return Ok(self.stop(StopReason::Breakpoint(pc)));
+                }
+            }
+            self.skip_breakpoint = false;
+            let opcode = chip.opcode_at(pc).unwrap_or(0);
+
+            match chip.step() {
+                Ok(StepResult::Ran(cost)) => spent += cost,
+                Ok(StepResult::Exited) => return Ok(Some(StopReason::Exited(pc))),
+                Err(e) if self.break_on_error => return Ok(self.stop(StopReason::Error(e))),
+                Err(e) => return Err(e),
+            }
+
+            let watch_hit = chip
+                .memory_accesses()
+                .iter()
+                .find(|a| self.watchpoints.iter().any(|w| w.matches(a)))
+                .copied();
+            if let Some(access) = watch_hit {
+                return Ok(self.stop(StopReason::Watchpoint { pc, opcode, access }));
+            }
+
+            let mut triggered = None;
+            for (condition, last) in self.conditions.iter_mut() {
+                let now = condition.eval(chip);
+                if now && !*last && triggered.is_none() {
+                    triggered = Some(condition.to_string());
+                }
+                *last = now;
+            }
+            if let Some(condition) = triggered {
+                return Ok(self.stop(StopReason::Condition {
+                    pc,
+                    opcode,
+                    condition,
+                }));
+            }
+
+            let pc = chip.pc();
+            let done = match self.mode {
+                StepMode::Continue => false,
+                StepMode::Into => true,
+                StepMode::Over { return_pc, sp } => pc == return_pc && chip.sp() == sp,
+                StepMode::Out { sp } => chip.sp() < sp,
+                StepMode::RunTo(addr) => pc == addr,
+            };
+            if done {
+                return Ok(self.stop(StopReason::Step(pc)));
+            }
+        }
+        self.overrun = spent - budget;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timing;
+
+    const ROM: [u8; 14] = [
+        0x60, 0x01, // 200: LD V0, 1
+        0x22, 0x08, // 202: CALL 0x208
+        0x71, 0x01, // 204: ADD V1, 1
+        0x12, 0x02, // 206: JP 0x202
+        0x72, 0x01, // 208: ADD V2, 1
+        0x72, 0x01, // 20A: ADD V2, 1
+        0x00, 0xEE, // 20C: RET
+    ];
+
+    fn chip() -> Chip8 {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("chip8").unwrap();
+        chip.load_rom(ROM.to_vec(), 0x200).unwrap();
+        chip
+    }
+
+    // Where a run() stopped on a breakpoint or step
+    fn stopped_at(debugger: &mut Debugger, chip: &mut Chip8) -> Option<u16> {
+        match debugger.run(chip, 100).unwrap() {
+            Some(StopReason::Breakpoint(pc)) | Some(StopReason::Step(pc)) => Some(pc),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut chip = chip();
+        let mut debugger = Debugger::new();
+        debugger.set_breakpoint(0x208, None);
+        let stop = debugger.run(&mut chip, 100).unwrap();
+        assert!(matches!(stop, Some(StopReason::Breakpoint(0x208))));
+        assert!(debugger.is_paused());
+        assert!(debugger.run(&mut chip, 100).unwrap().is_none());
+
+        // resuming runs the instruction under the breakpoint instead of stopping again
+        debugger.resume();
+        assert!(debugger.run(&mut chip, 3).unwrap().is_none());
+        assert_eq!((chip.pc(), chip.v()[2]), (0x204, 2));
+
+        // conditional breakpoints only stop when the condition holds
+        debugger.remove_breakpoint(0x208);
+        debugger.set_breakpoint(0x206, Some(Condition::parse("V1 == 3").unwrap()));
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x206));
+        assert_eq!(chip.v()[1], 3);
+    }
+
+    #[test]
+    fn steps() {
+        let mut chip = chip();
+        let mut debugger = Debugger::new();
+        debugger.pause();
+
+        debugger.step_into();
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x202));
+
+        // the whole call runs as one step
+        debugger.step_over(&chip);
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x204));
+        assert_eq!(chip.v()[2], 2);
+
+        // step over anything else is step into
+        debugger.step_over(&chip);
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x206));
+
+        debugger.run_to(0x20A);
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x20A));
+        assert_eq!((chip.sp(), chip.v()[2]), (1, 3));
+
+        debugger.step_out(&chip);
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x204));
+        assert_eq!((chip.sp(), chip.v()[2]), (0, 4));
+
+        // outside a subroutine step out is step into
+        debugger.step_out(&chip);
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x206));
+    }
+
+    #[test]
+    fn step_over_at_the_top_of_memory() {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("xochip").unwrap();
+        chip.load_rom(ROM.to_vec(), 0x200).unwrap();
+        // CALL 0x208 at 0xFFFE returns to 0x0000
+        chip.memory[0xFFFE..].copy_from_slice(&[0x22, 0x08]);
+        chip.pc = 0xFFFE;
+        let mut debugger = Debugger::new();
+        debugger.pause();
+        debugger.step_over(&chip);
+        assert_eq!(stopped_at(&mut debugger, &mut chip), Some(0x0000));
+        assert_eq!(chip.v()[2], 2);
+    }
+
+    #[test]
+    fn budget_overrun() {
+        let mut chip = chip();
+        let mut quirks = chip.quirks_mode().clone();
+        quirks.timing = Timing::CosmacVip;
+        chip.set_quirks_mode(quirks);
+        let mut debugger = Debugger::new();
+
+        // one instruction costs more than the budget, the next run pays it back first
+        let cost = Timing::CosmacVip.cost(0x6001, Default::default());
+        assert!(debugger.run(&mut chip, 1).unwrap().is_none());
+        assert_eq!(chip.pc(), 0x202);
+        for _ in 1..cost {
+            assert!(debugger.run(&mut chip, 1).unwrap().is_none());
+            assert_eq!(chip.pc(), 0x202);
+        }
+        debugger.run(&mut chip, 1).unwrap();
+        assert_eq!(chip.pc(), 0x208);
+    }
+}
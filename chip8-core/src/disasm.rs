@@ -0,0 +1,612 @@
+use crate::quirks::Mode;
+use crate::symbols::SymbolTable;
+use crate::{HIRES_ENTRY, HIRES_SWITCH};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/*
+   Opcode decoding for listings, the debugger and traces.
+
+   Mnemonics follow the opcode comments in Chip8::step(). Every instruction records the
+   first platform that defines it, so callers can flag opcodes that are invalid for the
+   mode being emulated. Unknown opcodes decode as "DW 0x....".
+
+   Decoding takes the mode because CHIP-8X reuses Bnnn for its colour opcodes, and
+   CHIP-8X's 02A0 and HIRES CHIP-8's 0230 are MEGA-CHIP palette loads elsewhere.
+*/
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Platform {
+    Chip8,
+    // SuperChip 1.1
+    SuperChip,
+    // SuperChip 1.0, without the scroll opcodes
+    SuperChip10,
+    XoChip,
+    // CHIP-8 plus its own extensions, none of SuperChip's
+    Chip8X,
+    // SuperChip plus the 256x192 colour display, none of XO-CHIP's
+    MegaChip,
+    // CHIP-8 plus the 64x64 display clear
+    HiresChip8,
+}
+
+impl Platform {
+    pub fn of_mode(mode: Mode) -> Self {
+        match mode {
+            Mode::Chip8Modern | Mode::CosmacVip | Mode::TwoPage => Platform::Chip8,
+            Mode::SuperChipModern | Mode::SuperChipLegacy => Platform::SuperChip,
+            Mode::SuperChip10 => Platform::SuperChip10,
+            Mode::XoChip => Platform::XoChip,
+            Mode::Chip8X => Platform::Chip8X,
+            Mode::MegaChip => Platform::MegaChip,
+            Mode::HiresChip8 => Platform::HiresChip8,
+        }
+    }
+
+    // Whether this platform has the opcodes of `other`
+    pub fn includes(&self, other: Platform) -> bool {
+        use Platform::*;
+        match (self, other) {
+            (_, Chip8) => true,
+            (Chip8X, other) => other == Chip8X,
+            (_, Chip8X) => false,
+            (HiresChip8, other) => other == HiresChip8,
+            (_, HiresChip8) => false,
+            (SuperChip10, other) => other == SuperChip10,
+            (MegaChip, other) => other != XoChip,
+            (_, MegaChip) => false,
+            (SuperChip, XoChip) => false,
+            (Chip8, _) => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub opcode: u16,
+    // second word of the 4 byte F000 nnnn and 01nn nnnn forms
+    pub operand: Option<u16>,
+    pub mnemonic: String,
+    // first platform defining this opcode, None if no platform does
+    pub since: Option<Platform>,
+}
+
+impl Instruction {
+    pub fn size(&self) -> u16 {
+        match self.operand {
+            Some(_) => 4,
+            None => 2,
+        }
+    }
+
+    pub fn valid_in(&self, mode: Mode) -> bool {
+        match self.since {
+            Some(since) => Platform::of_mode(mode).includes(since),
+            None => false,
+        }
+    }
+
+    // Address operand of jumps, calls and I loads
+    pub fn target(&self) -> Option<u16> {
+        let nnn = self.opcode & 0x0FFF;
+        match self.opcode & 0xF000 {
+            // CHIP-8X colour zones
+            0xB000 if self.since == Some(Platform::Chip8X) => None,
+            0x1000 | 0x2000 | 0xA000 | 0xB000 => Some(nnn),
+            0xF000 => self.operand,
+            _ => None,
+        }
+    }
+
+    // The mnemonic with the address operand replaced by its symbol, eg. "CALL draw_player"
+    pub fn symbolic(&self, symbols: &SymbolTable) -> String {
+        let Some(target) = self.target() else {
+            return self.mnemonic.clone();
+        };
+        let Some(name) = symbols.describe(target) else {
+            return self.mnemonic.clone();
+        };
+        match self.opcode & 0xF000 {
+            0x1000 => format!("JP {}", name),
+            0x2000 => format!("CALL {}", name),
+            0xB000 => format!("JP V0, {}", name),
+            _ => format!("LD I, {}", name),
+        }
+    }
+
+    // Where execution can continue after this instruction (see CodeMap)
+    fn flow(&self, addr: u16) -> Flow {
+        let nnn = self.opcode & 0x0FFF;
+        match self.opcode & 0xF000 {
+            0x0000 if self.opcode == 0x00EE || self.opcode == 0x00FD => Flow::Stop,
+            0x1000 => Flow::Jump(nnn),
+            0x2000 => Flow::Call(nnn),
+            0x3000 | 0x4000 => Flow::Skip,
+            0x5000 | 0x9000 if self.opcode & 0xF == 0 => Flow::Skip,
+            0xB000 if self.since != Some(Platform::Chip8X) => Flow::Stop,
+            0xE000 if self.since.is_some() => Flow::Skip,
+            _ if self.since.is_none() => Flow::Stop,
+            _ => Flow::Next(addr.wrapping_add(self.size())),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)
+    }
+}
+
+enum Flow {
+    Next(u16),
+    Jump(u16),
+    Call(u16),
+    Skip,
+    Stop,
+}
+
+/*
+   First platform defining an opcode, None if no platform does. This is the per-mode
+   opcode whitelist the core enforces, kept free of allocations as it runs every step
+*/
+pub fn platform_of(opcode: u16, mode: Mode) -> Option<Platform> {
+    let x = get_x!(opcode);
+    let n = get_n!(opcode);
+    let kk = get_kk!(opcode);
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x02A0 if mode == Mode::Chip8X => Some(Platform::Chip8X),
+            0x0230 if mode == Mode::HiresChip8 => Some(Platform::HiresChip8),
+            0x0010 | 0x0011 | 0x00B0..=0x00BF => Some(Platform::MegaChip),
+            // long I load, palette, sprite size, alpha
+            0x0100..=0x05FF => Some(Platform::MegaChip),
+            // sound, blend mode, collision colour
+            0x0600..=0x060F | 0x0700 | 0x0800..=0x0805 | 0x0900..=0x09FF => {
+                Some(Platform::MegaChip)
+            }
+            0x00C0..=0x00CF => Some(Platform::SuperChip),
+            0x00D0..=0x00DF => Some(Platform::XoChip),
+            0x00E0 | 0x00EE => Some(Platform::Chip8),
+            0x00FB | 0x00FC => Some(Platform::SuperChip),
+            0x00FD..=0x00FF => Some(Platform::SuperChip10),
+            _ => None,
+        },
+        0x5000 => match n {
+            0x0 => Some(Platform::Chip8),
+            0x1 => Some(Platform::Chip8X),
+            0x2 | 0x3 => Some(Platform::XoChip),
+            _ => None,
+        },
+        0x8000 => match n {
+            0x0..=0x7 | 0xE => Some(Platform::Chip8),
+            _ => None,
+        },
+        0x9000 if n != 0 => None,
+        0xB000 if mode == Mode::Chip8X => Some(Platform::Chip8X),
+        0xE000 => match kk {
+            0x9E | 0xA1 => Some(Platform::Chip8),
+            // second keypad
+            0xF2 | 0xF5 => Some(Platform::Chip8X),
+            _ => None,
+        },
+        0xF000 => match (opcode, kk) {
+            (0xF000, _) | (0xF002, _) => Some(Platform::XoChip),
+            (_, 0x01) | (_, 0x3A) => Some(Platform::XoChip),
+            (_, 0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65) => {
+                Some(Platform::Chip8)
+            }
+            (_, 0x30) => Some(Platform::SuperChip10),
+            (_, 0xF8 | 0xFB) => Some(Platform::Chip8X),
+            // SCHIP only has 8 flags, XO-CHIP extends them to 16
+            (_, 0x75 | 0x85) if x <= 7 => Some(Platform::SuperChip10),
+            (_, 0x75 | 0x85) => Some(Platform::XoChip),
+            _ => None,
+        },
+        _ => Some(Platform::Chip8),
+    }
+}
+
+/*
+   Decode one instruction. `next` is the word following the opcode and is only used by
+   the XO-CHIP F000 nnnn and MEGA-CHIP 01nn nnnn long loads
+*/
+pub fn decode(opcode: u16, next: u16, mode: Mode) -> Instruction {
+    let x = get_x!(opcode);
+    let y = get_y!(opcode);
+    let n = get_n!(opcode);
+    let nnn = get_nnn!(opcode);
+    let kk = get_kk!(opcode);
+
+    let since = platform_of(opcode, mode);
+    let mut operand = None;
+    let mnemonic = match opcode & 0xF000 {
+        _ if since.is_none() => format!("DW 0x{:04X}", opcode),
+        0x0000 => match opcode {
+            0x02A0 if since == Some(Platform::Chip8X) => "BGCOL".to_string(),
+            0x0230 if since == Some(Platform::HiresChip8) => "HCLS".to_string(),
+            0x0010 => "MEGAOFF".to_string(),
+            0x0011 => "MEGAON".to_string(),
+            0x00B0..=0x00BF => format!("SCU {}", n),
+            0x0100..=0x01FF => {
+                operand = Some(next);
+                format!("LD I, 0x{:02X}{:04X}", kk, next)
+            }
+            0x0200..=0x02FF => format!("LDPAL {}", kk),
+            0x0300..=0x03FF => format!("SPRW {}", kk),
+            0x0400..=0x04FF => format!("SPRH {}", kk),
+            0x0500..=0x05FF => format!("ALPHA 0x{:02X}", kk),
+            0x0600..=0x060F => format!("DIGISND {}", n),
+            0x0700 => "STOPSND".to_string(),
+            0x0800..=0x0805 => format!("BMODE {}", n),
+            0x0900..=0x09FF => format!("CCOL 0x{:02X}", kk),
+            0x00C0..=0x00CF => format!("SCD {}", n),
+            0x00D0..=0x00DF => format!("SCU {}", n),
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            0x00FB => "SCR".to_string(),
+            0x00FC => "SCL".to_string(),
+            0x00FD => "EXIT".to_string(),
+            0x00FE => "LOW".to_string(),
+            _ => "HIGH".to_string(),
+        },
+        0x1000 => format!("JP 0x{:03X}", nnn),
+        0x2000 => format!("CALL 0x{:03X}", nnn),
+        0x3000 => format!("SE V{:X}, 0x{:02X}", x, kk),
+        0x4000 => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        0x5000 => match n {
+            0x0 => format!("SE V{:X}, V{:X}", x, y),
+            0x1 => format!("ADDN V{:X}, V{:X}", x, y),
+            0x2 => format!("SAVE V{:X} - V{:X}", x, y),
+            _ => format!("LOAD V{:X} - V{:X}", x, y),
+        },
+        0x6000 => format!("LD V{:X}, 0x{:02X}", x, kk),
+        0x7000 => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        0x8000 => {
+            let op = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                _ => "SHL",
+            };
+            format!("{} V{:X}, V{:X}", op, x, y)
+        }
+        0x9000 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, 0x{:03X}", nnn),
+        0xB000 if since == Some(Platform::Chip8X) => match n {
+            0 => format!("COL V{:X}, V{:X}", x, y),
+            _ => format!("COL V{:X}, V{:X}, {}", x, y, n),
+        },
+        0xB000 => format!("JP V0, 0x{:03X}", nnn),
+        0xC000 => format!("RND V{:X}, 0x{:02X}", x, kk),
+        0xD000 => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE000 => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            0xF2 => format!("SKP2 V{:X}", x),
+            _ => format!("SKNP2 V{:X}", x),
+        },
+        _ => match (opcode, kk) {
+            (0xF000, _) => {
+                operand = Some(next);
+                format!("LD I, 0x{:04X}", next)
+            }
+            (0xF002, _) => "AUDIO".to_string(),
+            (_, 0x01) => format!("PLANE {}", x),
+            (_, 0x07) => format!("LD V{:X}, DT", x),
+            (_, 0x0A) => format!("LD V{:X}, K", x),
+            (_, 0x15) => format!("LD DT, V{:X}", x),
+            (_, 0x18) => format!("LD ST, V{:X}", x),
+            (_, 0x1E) => format!("ADD I, V{:X}", x),
+            (_, 0x29) => format!("LD F, V{:X}", x),
+            (_, 0x30) => format!("LD HF, V{:X}", x),
+            (_, 0x33) => format!("LD B, V{:X}", x),
+            (_, 0x3A) => format!("PITCH V{:X}", x),
+            (_, 0x55) => format!("LD [I], V{:X}", x),
+            (_, 0x65) => format!("LD V{:X}, [I]", x),
+            (_, 0x75) => format!("LD R, V{:X}", x),
+            (_, 0xF8) => format!("OUT V{:X}", x),
+            (_, 0xFB) => format!("IN V{:X}", x),
+            _ => format!("LD V{:X}, R", x),
+        },
+    };
+
+    Instruction {
+        opcode,
+        operand,
+        mnemonic,
+        since,
+    }
+}
+
+// Decode the instruction at `addr`, None if it runs past the end of memory
+pub fn decode_at(memory: &[u8], addr: u16, mode: Mode) -> Option<Instruction> {
+    let addr = addr as usize;
+    if addr + 1 >= memory.len() {
+        return None;
+    }
+    let opcode = (memory[addr] as u16) << 8 | memory[addr + 1] as u16;
+    let next = match memory.get(addr + 2..addr + 4) {
+        Some(b) => (b[0] as u16) << 8 | b[1] as u16,
+        None => 0,
+    };
+    Some(decode(opcode, next, mode))
+}
+
+/*
+   Code/data split of a ROM found by following control flow from the entry point.
+
+   Jumps and calls are followed, skips continue at both the next and the following
+   instruction, and RET/EXIT/JP V0 (computed jumps) end a path. Bytes never reached are
+   treated as data. This is a heuristic - self-modifying code and jump tables through
+   Bnnn will show up as data.
+*/
+pub struct CodeMap {
+    base: u16,
+    code: Vec<bool>,
+    labels: BTreeSet<u16>,
+}
+
+impl CodeMap {
+    pub fn analyze(rom: &[u8], base: u16, entry: u16, mode: Mode) -> Self {
+        let mut map = Self {
+            base,
+            code: vec![false; rom.len()],
+            labels: BTreeSet::new(),
+        };
+        let mut pending = vec![entry];
+        let mut visited = BTreeSet::new();
+        while let Some(addr) = pending.pop() {
+            if !map.contains(addr) || !visited.insert(addr) {
+                continue;
+            }
+            let offset = (addr - base) as usize;
+            let Some(instruction) = decode_at(rom, offset as u16, mode) else {
+                continue;
+            };
+            for i in 0..instruction.size() as usize {
+                if let Some(c) = map.code.get_mut(offset + i) {
+                    *c = true;
+                }
+            }
+            let next = addr.wrapping_add(instruction.size());
+            let flow = match instruction.opcode {
+                // HIRES CHIP-8 starts the program past its interpreter patch, see HIRES_SWITCH
+                HIRES_SWITCH if mode == Mode::HiresChip8 && addr == 0x200 => Flow::Jump(HIRES_ENTRY),
+                _ => instruction.flow(addr),
+            };
+            match flow {
+                Flow::Next(a) => pending.push(a),
+                Flow::Jump(a) => {
+                    map.labels.insert(a);
+                    pending.push(a);
+                }
+                Flow::Call(a) => {
+                    map.labels.insert(a);
+                    pending.push(a);
+                    pending.push(next);
+                }
+                Flow::Skip => {
+                    pending.push(next);
+                    // skipping over a long F000 nnnn skips all 4 bytes on XO-CHIP
+                    let skipped = match decode_at(rom, next.wrapping_sub(base), mode) {
+                        Some(i) => i.size(),
+                        None => 2,
+                    };
+                    pending.push(next.wrapping_add(skipped));
+                }
+                Flow::Stop => {}
+            }
+        }
+        map
+    }
+
+    fn contains(&self, addr: u16) -> bool {
+        addr >= self.base && ((addr - self.base) as usize) < self.code.len()
+    }
+
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.contains(addr) && self.code[(addr - self.base) as usize]
+    }
+
+    // Jump and call targets
+    pub fn is_label(&self, addr: u16) -> bool {
+        self.labels.contains(&addr)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &u16> {
+        self.labels.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Platform::*;
+
+    #[test]
+    fn platforms_of_opcodes() {
+        let table = [
+            (0x00E0, Mode::Chip8Modern, Some(Chip8)),
+            (0x00EE, Mode::CosmacVip, Some(Chip8)),
+            (0x00C1, Mode::Chip8Modern, Some(SuperChip)),
+            (0x00FB, Mode::Chip8Modern, Some(SuperChip)),
+            (0x00FD, Mode::Chip8Modern, Some(SuperChip10)),
+            (0x00FF, Mode::Chip8Modern, Some(SuperChip10)),
+            (0x00D1, Mode::Chip8Modern, Some(XoChip)),
+            (0x00B1, Mode::Chip8Modern, Some(MegaChip)),
+            (0x0011, Mode::Chip8Modern, Some(MegaChip)),
+            (0x0123, Mode::Chip8Modern, Some(MegaChip)),
+            (0x02A0, Mode::Chip8X, Some(Chip8X)),
+            (0x02A0, Mode::Chip8Modern, Some(MegaChip)),
+            (0x0230, Mode::HiresChip8, Some(HiresChip8)),
+            (0x0230, Mode::Chip8Modern, Some(MegaChip)),
+            (0x0000, Mode::Chip8Modern, None),
+            (0x0A00, Mode::MegaChip, None),
+            (0x1234, Mode::CosmacVip, Some(Chip8)),
+            (0x5120, Mode::Chip8Modern, Some(Chip8)),
+            (0x5121, Mode::Chip8Modern, Some(Chip8X)),
+            (0x5122, Mode::Chip8Modern, Some(XoChip)),
+            (0x5123, Mode::Chip8Modern, Some(XoChip)),
+            (0x5124, Mode::XoChip, None),
+            (0x812E, Mode::Chip8Modern, Some(Chip8)),
+            (0x8128, Mode::Chip8Modern, None),
+            (0x9120, Mode::Chip8Modern, Some(Chip8)),
+            (0x9121, Mode::Chip8Modern, None),
+            (0xB123, Mode::Chip8X, Some(Chip8X)),
+            (0xB123, Mode::Chip8Modern, Some(Chip8)),
+            (0xD120, Mode::CosmacVip, Some(Chip8)),
+            (0xD125, Mode::Chip8Modern, Some(Chip8)),
+            (0xE19E, Mode::Chip8Modern, Some(Chip8)),
+            (0xE1F2, Mode::Chip8Modern, Some(Chip8X)),
+            (0xE1F5, Mode::Chip8Modern, Some(Chip8X)),
+            (0xE1AB, Mode::Chip8Modern, None),
+            (0xF000, Mode::Chip8Modern, Some(XoChip)),
+            (0xF002, Mode::Chip8Modern, Some(XoChip)),
+            (0xF201, Mode::Chip8Modern, Some(XoChip)),
+            (0xF13A, Mode::Chip8Modern, Some(XoChip)),
+            (0xF10A, Mode::Chip8Modern, Some(Chip8)),
+            (0xF130, Mode::Chip8Modern, Some(SuperChip10)),
+            (0xF775, Mode::Chip8Modern, Some(SuperChip10)),
+            (0xF885, Mode::Chip8Modern, Some(XoChip)),
+            (0xF1F8, Mode::Chip8Modern, Some(Chip8X)),
+            (0xF1FB, Mode::Chip8Modern, Some(Chip8X)),
+            (0xF1FF, Mode::Chip8Modern, None),
+        ];
+        for (opcode, mode, expected) in table {
+            assert_eq!(
+                platform_of(opcode, mode),
+                expected,
+                "{:04X} in {:?}",
+                opcode,
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn platform_includes() {
+        let all = [
+            Chip8,
+            SuperChip10,
+            SuperChip,
+            XoChip,
+            Chip8X,
+            MegaChip,
+            HiresChip8,
+        ];
+        let table = [
+            (Chip8, vec![Chip8]),
+            (SuperChip10, vec![Chip8, SuperChip10]),
+            (SuperChip, vec![Chip8, SuperChip10, SuperChip]),
+            (XoChip, vec![Chip8, SuperChip10, SuperChip, XoChip]),
+            (Chip8X, vec![Chip8, Chip8X]),
+            (MegaChip, vec![Chip8, SuperChip10, SuperChip, MegaChip]),
+            (HiresChip8, vec![Chip8, HiresChip8]),
+        ];
+        for (platform, included) in table {
+            for other in all {
+                let expected = included.contains(&other);
+                assert_eq!(
+                    platform.includes(other),
+                    expected,
+                    "{:?} {:?}",
+                    platform,
+                    other
+                );
+            }
+        }
+        assert_eq!(Platform::of_mode(Mode::CosmacVip), Chip8);
+        assert_eq!(Platform::of_mode(Mode::SuperChipLegacy), SuperChip);
+    }
+
+    #[test]
+    fn decoding() {
+        let table = [
+            (0x00E0, 0, Mode::Chip8Modern, "CLS", 2, None),
+            (0x00FF, 0, Mode::Chip8Modern, "HIGH", 2, None),
+            (0x2345, 0, Mode::Chip8Modern, "CALL 0x345", 2, Some(0x345)),
+            (0x8126, 0, Mode::Chip8Modern, "SHR V1, V2", 2, None),
+            (0xB123, 0, Mode::Chip8Modern, "JP V0, 0x123", 2, Some(0x123)),
+            (0xB123, 0, Mode::Chip8X, "COL V1, V2, 3", 2, None),
+            (0xD120, 0, Mode::Chip8Modern, "DRW V1, V2, 0", 2, None),
+            (
+                0xF000,
+                0x1234,
+                Mode::XoChip,
+                "LD I, 0x1234",
+                4,
+                Some(0x1234),
+            ),
+            (0x0112, 0x3456, Mode::MegaChip, "LD I, 0x123456", 4, None),
+            (0xF165, 0, Mode::Chip8Modern, "LD V1, [I]", 2, None),
+            (0xFFFF, 0, Mode::Chip8Modern, "DW 0xFFFF", 2, None),
+        ];
+        for (opcode, next, mode, mnemonic, size, target) in table {
+            let instruction = decode(opcode, next, mode);
+            assert_eq!(instruction.mnemonic, mnemonic);
+            assert_eq!(instruction.size(), size, "{}", mnemonic);
+            assert_eq!(instruction.target(), target, "{}", mnemonic);
+        }
+        assert!(!decode(0x00FF, 0, Mode::Chip8Modern).valid_in(Mode::Chip8Modern));
+        assert!(decode(0x00FF, 0, Mode::SuperChip10).valid_in(Mode::SuperChip10));
+        assert!(!decode(0xFFFF, 0, Mode::XoChip).valid_in(Mode::XoChip));
+    }
+
+    fn code_map(rom: &[u8], mode: Mode) -> (Vec<bool>, Vec<u16>) {
+        let map = CodeMap::analyze(rom, 0x200, 0x200, mode);
+        let code = (0..rom.len() as u16)
+            .map(|i| map.is_code(0x200 + i))
+            .collect();
+        (code, map.labels().copied().collect())
+    }
+
+    #[test]
+    fn code_and_data() {
+        let rom = [
+            0x22, 0x06, // 200: CALL 0x206
+            0x30, 0x00, // 202: SE V0, 0
+            0x12, 0x04, // 204: JP 0x204
+            0x00, 0xEE, // 206: RET
+            0xAB, 0xCD, // 208: data
+        ];
+        let (code, labels) = code_map(&rom, Mode::Chip8Modern);
+        assert_eq!(code, [[true; 8].as_slice(), &[false; 2]].concat());
+        assert_eq!(labels, [0x204, 0x206]);
+
+        // skipping a long load skips all 4 bytes
+        let rom = [
+            0x30, 0x00, // 200: SE V0, 0
+            0xF0, 0x00, 0x12, 0x34, // 202: LD I, 0x1234
+            0x12, 0x06, // 206: JP 0x206
+            0x00, 0xFD, // 208: EXIT, only reached by a 2 byte skip
+        ];
+        let (code, labels) = code_map(&rom, Mode::XoChip);
+        assert_eq!(code, [[true; 8].as_slice(), &[false; 2]].concat());
+        assert_eq!(labels, [0x206]);
+    }
+
+    #[test]
+    fn hires_switch() {
+        let mut rom = vec![0u8; HIRES_ENTRY as usize - 0x200 + 2];
+        rom[..2].copy_from_slice(&HIRES_SWITCH.to_be_bytes());
+        // JP to itself at HIRES_ENTRY
+        let entry = HIRES_ENTRY as usize - 0x200;
+        rom[entry..].copy_from_slice(&(0x1000 | HIRES_ENTRY).to_be_bytes());
+
+        let (code, labels) = code_map(&rom, Mode::HiresChip8);
+        assert_eq!(labels, [HIRES_ENTRY]);
+        assert!(code[entry]);
+
+        // anywhere else it's a plain jump
+        let (code, labels) = code_map(&rom, Mode::Chip8Modern);
+        assert_eq!(labels, [HIRES_SWITCH & 0xFFF]);
+        assert!(!code[entry]);
+    }
+}
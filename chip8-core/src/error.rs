@@ -3,10 +3,15 @@ use std::fmt;
 #[derive(Debug)]
 pub enum CoreErrorType {
     InvalidOpcode(u16, u16),
+    // (pc, opcode, mode label) an opcode of another mode
+    UnsupportedInModeOpcode(u16, u16, String),
     StackOverflow(u16, u16),
     InvalidMemoryPtr(u16, usize),
     InvalidMemoryAccess(u16, usize),
     InvalidRom(String),
+    InvalidState(String),
+    InvalidConfig(String),
+    InvalidLayer(usize),
 }
 impl fmt::Display for CoreErrorType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -14,6 +19,11 @@ impl fmt::Display for CoreErrorType {
             CoreErrorType::InvalidOpcode(pc, code) => {
                 write!(f, "Invalid Opcode: 0x{:04X}\nPC: 0x{:04X}", code, pc)
             }
+            CoreErrorType::UnsupportedInModeOpcode(pc, code, ref mode) => write!(
+                f,
+                "Opcode 0x{:04X} is not supported in {}\nPC: 0x{:04X}",
+                code, mode, pc
+            ),
             CoreErrorType::StackOverflow(pc, addr) => {
                 write!(f, "Stack Overflow - SP:0x{:04X}\nPC: 0x{:04X} ", addr, pc)
             }
@@ -26,6 +36,30 @@ impl fmt::Display for CoreErrorType {
                 addr, pc
             ),
             CoreErrorType::InvalidRom(ref err_str) => write!(f, "Invalid ROM: {}", err_str),
+            CoreErrorType::InvalidState(ref err_str) => {
+                write!(f, "Invalid save state: {}", err_str)
+            }
+            CoreErrorType::InvalidConfig(ref err_str) => {
+                write!(f, "Invalid configuration: {}", err_str)
+            }
+            CoreErrorType::InvalidLayer(layer) => write!(f, "Invalid display layer: {}", layer),
+        }
+    }
+}
+
+impl CoreErrorType {
+    // PC recorded with the error, if it has one
+    pub fn pc(&self) -> Option<u16> {
+        match *self {
+            CoreErrorType::InvalidOpcode(pc, _)
+            | CoreErrorType::UnsupportedInModeOpcode(pc, _, _)
+            | CoreErrorType::StackOverflow(pc, _)
+            | CoreErrorType::InvalidMemoryPtr(pc, _)
+            | CoreErrorType::InvalidMemoryAccess(pc, _) => Some(pc),
+            CoreErrorType::InvalidRom(_)
+            | CoreErrorType::InvalidState(_)
+            | CoreErrorType::InvalidConfig(_)
+            | CoreErrorType::InvalidLayer(_) => None,
         }
     }
 }
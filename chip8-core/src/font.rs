@@ -0,0 +1,349 @@
+/*
+   Built in font sets.
+
+   The font sits at FONT_OFFSET: 16 small glyphs of 5 bytes for 0-F (Fx29), then up to
+   16 big glyphs of 10 bytes (Fx30). Sets without big glyphs, or fewer than 16 of them,
+   leave the rest zero, eg. the HP-48 SuperChip only has big 0-9.
+
+   Each Quirks profile picks its set, a custom one can be loaded from a file of the
+   same layout: 80 bytes of small glyphs followed by any number of big ones.
+*/
+
+use crate::types::FONT_OFFSET;
+
+pub const SMALL_GLYPH: usize = 5;
+pub const BIG_GLYPH: usize = 10;
+pub const SMALL_SIZE: usize = 16 * SMALL_GLYPH;
+pub const FONT_SIZE: usize = SMALL_SIZE + 16 * BIG_GLYPH;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Font {
+    Vip,
+    Dream6800,
+    Eti660,
+    FishNChips,
+    Schip,
+    Octo,
+}
+
+// Fx29: the small glyph of digit
+pub fn small_glyph(digit: u8) -> usize {
+    FONT_OFFSET + (digit & 0xF) as usize * SMALL_GLYPH
+}
+
+// Fx30: the big glyph of digit
+pub fn big_glyph(digit: u8) -> usize {
+    FONT_OFFSET + SMALL_SIZE + (digit & 0xF) as usize * BIG_GLYPH
+}
+
+// A font file as the FONT_SIZE bytes to load at FONT_OFFSET
+pub fn parse_custom(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() < SMALL_SIZE
+        || bytes.len() > FONT_SIZE
+        || !(bytes.len() - SMALL_SIZE).is_multiple_of(BIG_GLYPH)
+    {
+        return Err(format!(
+            "font file is {} bytes, expected {} of small glyphs and up to 16 big glyphs of {}",
+            bytes.len(),
+            SMALL_SIZE,
+            BIG_GLYPH
+        ));
+    }
+    let mut font = bytes.to_vec();
+    font.resize(FONT_SIZE, 0);
+    Ok(font)
+}
+
+impl Font {
+    pub fn id(&self) -> u8 {
+        match self {
+            Font::Vip => 0,
+            Font::Dream6800 => 1,
+            Font::Eti660 => 2,
+            Font::FishNChips => 3,
+            Font::Schip => 4,
+            Font::Octo => 5,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Font> {
+        match id {
+            0 => Some(Font::Vip),
+            1 => Some(Font::Dream6800),
+            2 => Some(Font::Eti660),
+            3 => Some(Font::FishNChips),
+            4 => Some(Font::Schip),
+            5 => Some(Font::Octo),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Font::Vip => "vip",
+            Font::Dream6800 => "dream6800",
+            Font::Eti660 => "eti660",
+            Font::FishNChips => "fishnchips",
+            Font::Schip => "schip",
+            Font::Octo => "octo",
+        }
+    }
+
+    // Also takes the fontStyle names of the chip-8-database
+    pub fn from_name(name: &str) -> Option<Font> {
+        match name.to_lowercase().as_str() {
+            "vip" => Some(Font::Vip),
+            "dream6800" => Some(Font::Dream6800),
+            "eti660" => Some(Font::Eti660),
+            "fishnchips" | "fish" => Some(Font::FishNChips),
+            "schip" => Some(Font::Schip),
+            "octo" => Some(Font::Octo),
+            _ => None,
+        }
+    }
+
+    // The FONT_SIZE bytes to load at FONT_OFFSET
+    pub fn data(&self) -> Vec<u8> {
+        let (small, big): (&[u8], &[u8]) = match self {
+            Font::Vip => (&VIP_SMALL, &[]),
+            Font::Dream6800 => (&DREAM6800_SMALL, &[]),
+            Font::Eti660 => (&ETI660_SMALL, &[]),
+            Font::FishNChips => (&FISHNCHIPS_SMALL, &FISHNCHIPS_BIG),
+            Font::Schip => (&OCTO_SMALL, &SCHIP_BIG),
+            Font::Octo => (&OCTO_SMALL, &OCTO_BIG),
+        };
+        let mut font = [small, big].concat();
+        font.resize(FONT_SIZE, 0);
+        font
+    }
+}
+
+// The COSMAC VIP interpreter's, also used by the other VIP based modes
+const VIP_SMALL: [u8; SMALL_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const DREAM6800_SMALL: [u8; SMALL_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const ETI660_SMALL: [u8; SMALL_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const FISHNCHIPS_SMALL: [u8; SMALL_SIZE] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const FISHNCHIPS_BIG: [u8; 16 * BIG_GLYPH] = [
+    0x7C, 0xC6, 0xCE, 0xDE, 0xD6, 0xF6, 0xE6, 0xC6, 0x7C, 0x00, // 0
+    0x10, 0x30, 0xF0, 0x30, 0x30, 0x30, 0x30, 0x30, 0xFC, 0x00, // 1
+    0x78, 0xCC, 0xCC, 0x0C, 0x18, 0x30, 0x60, 0xCC, 0xFC, 0x00, // 2
+    0x78, 0xCC, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0xCC, 0x78, 0x00, // 3
+    0x0C, 0x1C, 0x3C, 0x6C, 0xCC, 0xFE, 0x0C, 0x0C, 0x1E, 0x00, // 4
+    0xFC, 0xC0, 0xC0, 0xC0, 0xF8, 0x0C, 0x0C, 0xCC, 0x78, 0x00, // 5
+    0x38, 0x60, 0xC0, 0xC0, 0xF8, 0xCC, 0xCC, 0xCC, 0x78, 0x00, // 6
+    0xFE, 0xC6, 0xC6, 0x06, 0x0C, 0x18, 0x30, 0x30, 0x30, 0x00, // 7
+    0x78, 0xCC, 0xCC, 0xEC, 0x78, 0xDC, 0xCC, 0xCC, 0x78, 0x00, // 8
+    0x7C, 0xC6, 0xC6, 0xC6, 0x7C, 0x18, 0x18, 0x30, 0x70, 0x00, // 9
+    0x30, 0x78, 0xCC, 0xCC, 0xCC, 0xFC, 0xCC, 0xCC, 0xCC, 0x00, // A
+    0xFC, 0x66, 0x66, 0x66, 0x7C, 0x66, 0x66, 0x66, 0xFC, 0x00, // B
+    0x3C, 0x66, 0xC6, 0xC0, 0xC0, 0xC0, 0xC6, 0x66, 0x3C, 0x00, // C
+    0xF8, 0x6C, 0x66, 0x66, 0x66, 0x66, 0x66, 0x6C, 0xF8, 0x00, // D
+    0xFE, 0x62, 0x60, 0x64, 0x7C, 0x64, 0x60, 0x62, 0xFE, 0x00, // E
+    0xFE, 0x66, 0x62, 0x64, 0x7C, 0x64, 0x60, 0x60, 0xF0, 0x00, // F
+];
+
+// CHIP-48 and SuperChip took these small glyphs over, Octo kept them
+const OCTO_SMALL: [u8; SMALL_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// The HP-48 SuperChip has big digits only
+const SCHIP_BIG: [u8; 10 * BIG_GLYPH] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+const OCTO_BIG: [u8; 16 * BIG_GLYPH] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CoreErrorType;
+    use crate::Chip8;
+
+    const FONTS: [Font; 6] = [
+        Font::Vip,
+        Font::Dream6800,
+        Font::Eti660,
+        Font::FishNChips,
+        Font::Schip,
+        Font::Octo,
+    ];
+
+    #[test]
+    fn custom_font_sizes() {
+        // 80 bytes of small glyphs and 0 to 16 big ones
+        for big in 0..=16 {
+            let bytes: Vec<u8> = (1..=(SMALL_SIZE + big * BIG_GLYPH) as u8).collect();
+            let font = parse_custom(&bytes).unwrap();
+            assert_eq!(font.len(), FONT_SIZE);
+            assert_eq!(font[..bytes.len()], bytes[..]);
+            assert!(font[bytes.len()..].iter().all(|&b| b == 0));
+        }
+        for len in [0, 79, 81, 85, 89, 91, 235, 239, 241, 250, 256] {
+            let error = parse_custom(&vec![0xF0; len]).unwrap_err();
+            assert!(
+                error.starts_with(&format!("font file is {} bytes", len)),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn custom_font_replaces_the_set() {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("superchip").unwrap();
+        let mut bytes = vec![0x11; SMALL_SIZE];
+        bytes.extend([0x22; BIG_GLYPH]);
+        chip.set_custom_font(Some(&bytes)).unwrap();
+        assert_eq!(chip.memory[small_glyph(0xF)], 0x11);
+        assert_eq!(chip.memory[big_glyph(0)], 0x22);
+        assert_eq!(chip.memory[big_glyph(1)], 0);
+
+        // a bad file leaves the font alone
+        let error = chip.set_custom_font(Some(&bytes[1..])).unwrap_err();
+        let invalid = matches!(error.error_type, CoreErrorType::InvalidConfig(_));
+        assert!(invalid, "{:?}", error);
+        assert_eq!(chip.memory[big_glyph(0)], 0x22);
+
+        chip.set_custom_font(None).unwrap();
+        let octo = Font::Octo.data();
+        assert_eq!(chip.memory[FONT_OFFSET..FONT_OFFSET + FONT_SIZE], octo[..]);
+    }
+
+    #[test]
+    fn built_in_fonts() {
+        for font in FONTS {
+            assert_eq!(Font::from_id(font.id()), Some(font));
+            assert_eq!(Font::from_name(font.name()), Some(font));
+            let data = font.data();
+            assert_eq!(data.len(), FONT_SIZE);
+            // every set has all 16 small glyphs
+            assert!(data[..SMALL_SIZE]
+                .chunks(SMALL_GLYPH)
+                .all(|g| g.iter().any(|&b| b != 0)));
+        }
+        // the HP-48 set stops at a big 9
+        let schip = Font::Schip.data();
+        assert!(schip[big_glyph(9) - FONT_OFFSET..][..BIG_GLYPH]
+            .iter()
+            .any(|&b| b != 0));
+        assert!(schip[big_glyph(0xA) - FONT_OFFSET..]
+            .iter()
+            .all(|&b| b == 0));
+        assert_eq!(Font::from_name("Fish"), Some(Font::FishNChips));
+    }
+}
@@ -0,0 +1,618 @@
+use crate::debugger::Debugger;
+use crate::Chip8;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/*
+   GDB remote serial protocol stub.
+
+   The server never blocks: the frontend calls poll() once per frame, which accepts a
+   client, answers whatever packets have arrived and reports when a continue/step has
+   stopped. Execution itself stays with the Debugger, so breakpoints set from gdb and
+   from the frontend are the same breakpoints.
+
+   Registers, in `g` packet order and big-endian like the rest of the machine:
+   v0-vf (8 bit), i (32), pc (16), sp (8, stack depth), dt (8), st (8)
+*/
+
+const REGISTERS: [(&str, usize); 21] = [
+    ("v0", 1),
+    ("v1", 1),
+    ("v2", 1),
+    ("v3", 1),
+    ("v4", 1),
+    ("v5", 1),
+    ("v6", 1),
+    ("v7", 1),
+    ("v8", 1),
+    ("v9", 1),
+    ("va", 1),
+    ("vb", 1),
+    ("vc", 1),
+    ("vd", 1),
+    ("ve", 1),
+    ("vf", 1),
+    ("i", 4),
+    ("pc", 2),
+    ("sp", 1),
+    ("dt", 1),
+    ("st", 1),
+];
+
+// Largest packet gdb may send or receive, advertised in qSupported
+const PACKET_SIZE: usize = 0x4000;
+// Bytes that fit in an `m` reply: two hex digits each, less the $ and #xx framing
+const MAX_READ: usize = (PACKET_SIZE - 4) / 2;
+
+// SIGTRAP and SIGINT, as gdb expects them in stop replies
+const SIGTRAP: u8 = 5;
+const SIGINT: u8 = 2;
+
+fn target_xml() -> String {
+    let mut regs = String::new();
+    for (num, (name, size)) in REGISTERS.iter().enumerate() {
+        let kind = match *name {
+            "pc" => "code_ptr",
+            "i" => "data_ptr",
+            _ => "uint8",
+        };
+        regs.push_str(&format!(
+            "    <reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>\n",
+            name,
+            size * 8,
+            kind,
+            num
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n<target version=\"1.0\">\n  <feature name=\"org.chip8.core\">\n{}  </feature>\n</target>\n",
+        regs
+    )
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(hex: &str) -> Option<usize> {
+    usize::from_str_radix(hex, 16).ok()
+}
+
+enum Incoming {
+    Packet(String),
+    // a bare 0x03 between packets
+    Interrupt,
+    // checksum mismatch, NAKed so the client sends it again
+    Corrupt,
+}
+
+struct Connection {
+    stream: TcpStream,
+    input: Vec<u8>,
+    no_ack: bool,
+    // a continue or step is in progress, report when the debugger stops
+    running: bool,
+}
+
+impl Connection {
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let mut body = Vec::with_capacity(data.len());
+        for b in data.bytes() {
+            if matches!(b, b'$' | b'#' | b'}' | b'*') {
+                body.push(b'}');
+                body.push(b ^ 0x20);
+            } else {
+                body.push(b);
+            }
+        }
+        let checksum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(&body);
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+
+        // replies are small, write them out in one go
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(&packet);
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+
+    fn ack(&mut self, ok: bool) -> io::Result<()> {
+        if self.no_ack {
+            return Ok(());
+        }
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(if ok { b"+" } else { b"-" });
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+
+    // Next complete packet or interrupt in the input
+    fn next_packet(&mut self) -> Option<Incoming> {
+        loop {
+            let first = *self.input.first()?;
+            match first {
+                0x03 => {
+                    self.input.remove(0);
+                    return Some(Incoming::Interrupt);
+                }
+                b'$' => {
+                    let end = self.input.iter().position(|b| *b == b'#')?;
+                    if self.input.len() < end + 3 {
+                        return None;
+                    }
+                    let body = &self.input[1..end];
+                    let sum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+                    let expected = std::str::from_utf8(&self.input[end + 1..end + 3])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                    let packet = if expected == Some(sum) {
+                        Incoming::Packet(String::from_utf8_lossy(body).to_string())
+                    } else {
+                        Incoming::Corrupt
+                    };
+                    self.input.drain(..end + 3);
+                    return Some(packet);
+                }
+                // acks from the client and line noise
+                _ => {
+                    self.input.remove(0);
+                }
+            }
+        }
+    }
+}
+
+pub struct GdbServer {
+    listener: TcpListener,
+    connection: Option<Connection>,
+}
+
+impl GdbServer {
+    // eg. bind("127.0.0.1:2331")
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            connection: None,
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    pub fn poll(&mut self, chip: &mut Chip8, debugger: &mut Debugger) -> io::Result<()> {
+        if self.connection.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.connection = Some(Connection {
+                        stream,
+                        input: vec![],
+                        no_ack: false,
+                        running: false,
+                    });
+                    // gdb expects the target to be stopped when it attaches
+                    debugger.pause();
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let Some(conn) = self.connection.as_mut() else {
+            return Ok(());
+        };
+        let result = serve(conn, chip, debugger);
+        if !matches!(result, Ok(true)) {
+            // client went away, let the program carry on without it
+            self.connection = None;
+            debugger.resume();
+        }
+        result.map(|_| ())
+    }
+
+    /*
+       Execution ended outside the debugger: the program exited (00FD) or a CoreError
+       stopped it. The frontend stops polling after either, so answer a pending
+       continue/step now or gdb waits forever
+    */
+    pub fn report_exit(&mut self) -> io::Result<()> {
+        self.report_stop("W00")
+    }
+
+    pub fn report_error(&mut self) -> io::Result<()> {
+        self.report_stop(&format!("S{:02x}", SIGTRAP))
+    }
+
+    fn report_stop(&mut self, reply: &str) -> io::Result<()> {
+        let Some(conn) = self.connection.as_mut() else {
+            return Ok(());
+        };
+        if !conn.running {
+            return Ok(());
+        }
+        conn.running = false;
+        let result = conn.send(reply);
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+}
+
+// Answer the packets that have arrived. Ok(false) once the client has disconnected
+fn serve(conn: &mut Connection, chip: &mut Chip8, debugger: &mut Debugger) -> io::Result<bool> {
+    let mut buf = [0u8; 4096];
+    loop {
+        match conn.stream.read(&mut buf) {
+            Ok(0) => return Ok(false),
+            Ok(n) => conn.input.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    while let Some(incoming) = conn.next_packet() {
+        let packet = match incoming {
+            Incoming::Packet(packet) => packet,
+            Incoming::Interrupt => {
+                debugger.pause();
+                conn.running = false;
+                conn.send(&format!("S{:02x}", SIGINT))?;
+                continue;
+            }
+            Incoming::Corrupt => {
+                conn.ack(false)?;
+                continue;
+            }
+        };
+        conn.ack(true)?;
+        match handle(&packet, conn, chip, debugger) {
+            Some(reply) => conn.send(&reply)?,
+            None if packet.starts_with('D') || packet.starts_with('k') => return Ok(false),
+            None => {}
+        }
+    }
+
+    if conn.running && debugger.is_paused() {
+        conn.running = false;
+        conn.send(&format!("S{:02x}", SIGTRAP))?;
+    }
+    Ok(true)
+}
+
+fn read_registers(chip: &Chip8) -> Vec<u8> {
+    let mut out = chip.v.clone();
+    out.extend_from_slice(&chip.i.to_be_bytes());
+    out.extend_from_slice(&chip.pc.to_be_bytes());
+    out.push(chip.sp as u8);
+    out.push(chip.dt);
+    out.push(chip.st);
+    out
+}
+
+fn write_register(chip: &mut Chip8, num: usize, bytes: &[u8]) -> bool {
+    let value = bytes.iter().fold(0u32, |v, b| (v << 8) | *b as u32);
+    match num {
+        0..=15 => chip.v[num] = value as u8,
+        16 => chip.i = value,
+        17 => chip.pc = value as u16,
+        18 if (value as usize) <= chip.stack.len() => chip.sp = value as u16,
+        19 => chip.dt = value as u8,
+        20 => chip.st = value as u8,
+        _ => return false,
+    }
+    true
+}
+
+/*
+   Answer one packet. None means no reply: either a continue/step that's answered
+   when the debugger stops, or a detach/kill that closes the connection
+*/
+fn handle(
+    packet: &str,
+    conn: &mut Connection,
+    chip: &mut Chip8,
+    debugger: &mut Debugger,
+) -> Option<String> {
+    let command = packet.get(..1).unwrap_or("");
+    let args = packet.get(1..).unwrap_or("");
+    let reply = match command {
+        "?" => format!("S{:02x}", SIGTRAP),
+        "g" => to_hex(&read_registers(chip)),
+        "G" => {
+            let Some(bytes) = from_hex(args) else {
+                return Some("E01".to_string());
+            };
+            let mut offset = 0;
+            for (num, (_, size)) in REGISTERS.iter().enumerate() {
+                let Some(value) = bytes.get(offset..offset + size) else {
+                    break;
+                };
+                if !write_register(chip, num, value) {
+                    return Some("E01".to_string());
+                }
+                offset += size;
+            }
+            "OK".to_string()
+        }
+        "p" => {
+            let Some(num) = parse_hex(args).filter(|n| *n < REGISTERS.len()) else {
+                return Some("E01".to_string());
+            };
+            let offset: usize = REGISTERS[..num].iter().map(|(_, size)| size).sum();
+            to_hex(&read_registers(chip)[offset..offset + REGISTERS[num].1])
+        }
+        "P" => {
+            let parsed = args
+                .split_once('=')
+                .and_then(|(num, value)| Some((parse_hex(num)?, from_hex(value)?)));
+            match parsed {
+                Some((num, value)) if write_register(chip, num, &value) => "OK".to_string(),
+                _ => "E01".to_string(),
+            }
+        }
+        "m" => {
+            let parsed = args
+                .split_once(',')
+                .and_then(|(addr, len)| Some((parse_hex(addr)?, parse_hex(len)?)));
+            match parsed {
+                Some((addr, len)) if addr < chip.memory.len() => match addr.checked_add(len) {
+                    Some(end) => {
+                        let end = end.min(addr + MAX_READ).min(chip.memory.len());
+                        to_hex(&chip.memory[addr..end])
+                    }
+                    None => "E01".to_string(),
+                },
+                _ => "E01".to_string(),
+            }
+        }
+        "M" => {
+            let parsed = args.split_once(':').and_then(|(range, data)| {
+                let (addr, len) = range.split_once(',')?;
+                Some((parse_hex(addr)?, parse_hex(len)?, from_hex(data)?))
+            });
+            let in_range = |addr: usize, len: usize| {
+                addr.checked_add(len)
+                    .is_some_and(|end| end <= chip.memory.len())
+            };
+            match parsed {
+                Some((addr, len, data)) if data.len() == len && in_range(addr, len) => {
+                    chip.memory[addr..addr + len].copy_from_slice(&data);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            }
+        }
+        "Z" | "z" => {
+            // only software breakpoints (type 0) are supported
+            let mut parts = args.split(',');
+            let kind = parts.next();
+            let addr = parts.next().and_then(parse_hex);
+            match (kind, addr) {
+                (Some("0"), Some(addr)) if addr <= 0xFFFF => {
+                    if command == "Z" {
+                        debugger.set_breakpoint(addr as u16, None);
+                    } else {
+                        debugger.remove_breakpoint(addr as u16);
+                    }
+                    "OK".to_string()
+                }
+                _ => String::new(),
+            }
+        }
+        "c" | "s" => {
+            if let Some(addr) = parse_hex(args) {
+                chip.pc = addr as u16;
+            }
+            if command == "c" {
+                debugger.resume();
+            } else {
+                debugger.step_into();
+            }
+            conn.running = true;
+            return None;
+        }
+        "D" => {
+            let _ = conn.send("OK");
+            return None;
+        }
+        "k" => return None,
+        "H" => "OK".to_string(),
+        "T" => "OK".to_string(),
+        _ => query(packet, conn),
+    };
+    Some(reply)
+}
+
+fn query(packet: &str, conn: &mut Connection) -> String {
+    if packet.starts_with("qSupported") {
+        return format!(
+            "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;swbreak+",
+            PACKET_SIZE
+        );
+    }
+    if packet == "QStartNoAckMode" {
+        conn.no_ack = true;
+        return "OK".to_string();
+    }
+    if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        let xml = target_xml();
+        let parsed = range
+            .split_once(',')
+            .and_then(|(offset, len)| Some((parse_hex(offset)?, parse_hex(len)?)));
+        let Some((offset, len)) = parsed else {
+            return "E01".to_string();
+        };
+        if offset >= xml.len() {
+            return "l".to_string();
+        }
+        let end = offset.saturating_add(len).min(xml.len());
+        let more = if end < xml.len() { "m" } else { "l" };
+        return format!("{}{}", more, &xml[offset..end]);
+    }
+    match packet {
+        "qAttached" => "1".to_string(),
+        "qC" => "QC1".to_string(),
+        "qfThreadInfo" => "m1".to_string(),
+        "qsThreadInfo" => "l".to_string(),
+        // anything else is unsupported, which gdb signals with an empty reply
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StopReason;
+    use std::time::{Duration, Instant};
+
+    struct Session {
+        server: GdbServer,
+        client: TcpStream,
+        chip: Chip8,
+        debugger: Debugger,
+    }
+
+    impl Session {
+        fn new() -> Self {
+            let server = GdbServer::bind("127.0.0.1:0").unwrap();
+            let client = TcpStream::connect(server.listener.local_addr().unwrap()).unwrap();
+            client.set_nonblocking(true).unwrap();
+            let mut chip = Chip8::new();
+            chip.set_core_mode("superchip").unwrap();
+            // CLS, EXIT
+            chip.load_rom(vec![0x00, 0xE0, 0x00, 0xFD], 0x200).unwrap();
+            let mut session = Self {
+                server,
+                client,
+                chip,
+                debugger: Debugger::new(),
+            };
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !session.server.is_connected() {
+                assert!(Instant::now() < deadline, "client never accepted");
+                session.poll();
+            }
+            session
+        }
+
+        fn poll(&mut self) {
+            self.server
+                .poll(&mut self.chip, &mut self.debugger)
+                .unwrap();
+        }
+
+        fn send_raw(&mut self, raw: &[u8]) {
+            self.client.write_all(raw).unwrap();
+        }
+
+        fn send(&mut self, body: &str) {
+            self.send_raw(packet(body).as_bytes());
+        }
+
+        // Poll until `expected` has arrived from the server
+        fn expect(&mut self, expected: &str) {
+            let mut received = Vec::new();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while received.len() < expected.len() {
+                assert!(
+                    Instant::now() < deadline,
+                    "got {:?}, wanted {:?}",
+                    String::from_utf8_lossy(&received),
+                    expected
+                );
+                self.poll();
+                let mut buf = [0u8; 256];
+                match self.client.read(&mut buf) {
+                    Ok(n) => received.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => panic!("{}", e),
+                }
+            }
+            assert_eq!(String::from_utf8_lossy(&received), expected);
+        }
+    }
+
+    fn packet(body: &str) -> String {
+        let sum = body.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        format!("${}#{:02x}", body, sum)
+    }
+
+    #[test]
+    fn bad_checksum_is_nakked() {
+        let mut s = Session::new();
+        s.send_raw(b"$g#00");
+        s.expect("-");
+        s.send("m200,2");
+        s.expect(&format!("+{}", packet("00e0")));
+    }
+
+    #[test]
+    fn memory_ranges_dont_overflow() {
+        let mut s = Session::new();
+        s.send("m1,ffffffffffffffff");
+        s.expect(&format!("+{}", packet("E01")));
+        s.send("Mffffffffffffffff,1:00");
+        s.expect(&format!("+{}", packet("E01")));
+        s.send("M200,1:12");
+        s.expect(&format!("+{}", packet("OK")));
+        assert_eq!(s.chip.memory[0x200], 0x12);
+    }
+
+    #[test]
+    fn reads_fit_in_a_packet() {
+        let mut s = Session::new();
+        s.chip.set_core_mode("megachip").unwrap();
+        s.send("m0,ffffff");
+        let expected = packet(&to_hex(&s.chip.memory[..MAX_READ]));
+        assert!(expected.len() <= PACKET_SIZE);
+        s.expect(&format!("+{}", expected));
+    }
+
+    #[test]
+    fn bad_register_writes_fail() {
+        let mut s = Session::new();
+        // 21 registers, sp (0xff) deeper than the stack
+        let mut regs = "00".repeat(16);
+        regs.push_str("000003000200ff0000");
+        s.send(&format!("G{}", regs));
+        s.expect(&format!("+{}", packet("E01")));
+        s.send("Pd=ff");
+        s.expect(&format!("+{}", packet("OK")));
+        s.send("P12=ff");
+        s.expect(&format!("+{}", packet("E01")));
+    }
+
+    #[test]
+    fn exit_and_error_are_reported() {
+        let mut s = Session::new();
+        s.send("c");
+        s.expect("+");
+        let stop = s.debugger.run(&mut s.chip, 100).unwrap();
+        assert!(matches!(stop, Some(StopReason::Exited(_))));
+        s.server.report_exit().unwrap();
+        s.expect(&packet("W00"));
+
+        // nothing is pending until the next continue/step
+        s.server.report_error().unwrap();
+        s.send("s");
+        s.expect("+");
+        s.server.report_error().unwrap();
+        s.expect(&packet("S05"));
+    }
+}
@@ -0,0 +1,1905 @@
+use crate::error::CoreErrorType::*;
+use access::{AccessKind, AccessLog, MemoryAccess};
+use chip8x::ColorBoard;
+use disasm::Platform;
+use megachip::{MegaScreen, SampleEvent};
+use quirks::Mode::*;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+#[macro_use]
+mod util;
+pub mod access;
+pub mod asm;
+pub mod chip8x;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod font;
+pub mod gdb;
+pub mod megachip;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+mod state;
+pub mod symbols;
+pub mod timing;
+pub mod trace;
+pub mod types;
+pub mod watch;
+
+pub use debugger::{Debugger, StopReason};
+pub use error::{CoreError, CoreErrorType};
+pub use quirks::{Mode, PlatformCheck, Quirks};
+pub use rewind::RewindBuffer;
+pub use rng::{Rng, RngKind};
+pub use state::STATE_VERSION;
+pub use symbols::SymbolTable;
+pub use timing::{Outcome, Timing};
+pub use trace::{TraceEntry, Tracer};
+
+pub const DISPLAY_ROWS: usize = 64;
+pub const DISPLAY_COLS: usize = 128;
+pub const DISPLAY_LAYERS: usize = 4;
+// RPL user flags of XO-CHIP, the HP-48 has the first 8 (see disasm::platform_of)
+pub const RPL_FLAGS: usize = 16;
+
+// The VIP interpreter keeps its stack, variables and display buffer from here to the end of memory
+const VIP_RESERVED_START: usize = 0xEA0;
+// HIRES CHIP-8 programs open with this jump into the interpreter's 64x64 setup, which
+// then starts them at HIRES_ENTRY
+pub const HIRES_SWITCH: u16 = 0x1260;
+pub const HIRES_ENTRY: u16 = 0x2C0;
+
+// What step() did
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StepResult {
+    // Ran an instruction (or waited on input or the display) at this cost, see Timing
+    Ran(u32),
+    // The program exited with 00FD, step() does nothing more until the machine is reset
+    Exited,
+}
+
+// Bytes in the XO-CHIP audio pattern buffer
+pub const PATTERN_SIZE: usize = 16;
+
+pub struct Sound {
+    pub pitch: u8,
+    pub pattern: Vec<u8>,
+    dirty: bool,
+}
+impl Sound {
+    pub fn new() -> Self {
+        Self {
+            pitch: 247,
+            pattern: vec![
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff,
+            ],
+            dirty: true,
+        }
+    }
+}
+impl Default for Sound {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Chip8 {
+    screen: Arc<Mutex<types::Screen>>,
+    memory: Vec<u8>,     // [u8; 2^16],
+    stack: Vec<u16>,     // [u16; 16],
+    keyboard: Vec<bool>, // [bool; 16],
+    keyboard2: Vec<bool>, // [bool; 16], CHIP-8X second keypad
+
+    v: Vec<u8>,   // 16 8-bit registers (note VF is a carry-flag register) = [u8; 16]
+    rpl: Vec<u8>, // 16 8-bit RPL user flags (Super-Chip has 8), persisted by the frontend
+    pc: u16,      // Program/Instruction counter
+    i: u32,       // Index register, 24 bits on MEGA-CHIP
+    sp: u16,      // Stack pointer
+    dt: u8,       // Delay timer
+    st: u8,       // Sound timer
+
+    hires_mode: bool,
+    tall_mode: bool, // 64x64 display of HIRES CHIP-8 and the two-page display
+    halt_input_register: u8,
+    halted_for_input: bool,
+    exited: bool,          // 00FD ran, pc stays on it
+    rpl_changed: bool,     // Fx75 ran since the last take_rpl_changed()
+    input_key: Option<u8>, // key pressed during a Fx0A wait with key_release_tone
+    waiting_for_vblank: bool,
+    quirks: Quirks,
+    sound: Sound,
+    bit_plane_selector: u8,
+    rng: Rng,
+    access_log: AccessLog,
+    cycles: u64, // instructions executed since reset
+    tracer: Option<Tracer>,
+    symbols: SymbolTable,
+    warnings: Vec<CoreError>, // platform violations under PlatformCheck::Warn
+    warned: BTreeSet<u16>,    // PCs already warned about
+    color_board: ColorBoard,  // CHIP-8X colours
+    port_in: Option<u8>,      // CHIP-8X input port byte waiting for FxFB
+    mega: MegaScreen,         // MEGA-CHIP display
+    sample_event: Option<SampleEvent>, // MEGA-CHIP sound for the frontend to pick up
+    custom_font: Option<Vec<u8>>,      // loaded instead of the quirks' font
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Self {
+        let mut c = Self {
+            screen: Arc::new(Mutex::new(vec![
+                vec![
+                    vec![false; DISPLAY_LAYERS];
+                    DISPLAY_COLS
+                ];
+                DISPLAY_ROWS
+            ])),
+            memory: vec![0u8; 1 << 16],
+            stack: vec![0u16; 16],
+            keyboard: vec![false; 16],
+            keyboard2: vec![false; 16],
+            v: vec![0u8; 16],
+            rpl: vec![0u8; RPL_FLAGS],
+            pc: 0x200,
+            i: 0,
+            sp: 0,
+            dt: 0,
+            st: 0,
+            hires_mode: false,
+            tall_mode: false,
+            halt_input_register: 0,
+            halted_for_input: false,
+            exited: false,
+            rpl_changed: false,
+            input_key: None,
+            waiting_for_vblank: false,
+            quirks: Quirks::new(XoChip),
+            sound: Sound::new(),
+            bit_plane_selector: 1,
+            rng: Rng::default(),
+            access_log: AccessLog::default(),
+            cycles: 0,
+            tracer: None,
+            symbols: SymbolTable::new(),
+            color_board: ColorBoard::new(),
+            port_in: None,
+            mega: MegaScreen::new(),
+            sample_event: None,
+            custom_font: None,
+            warnings: vec![],
+            warned: BTreeSet::new(),
+        };
+        c.load_font();
+        c
+    }
+    
+    pub fn reset(&mut self) {
+        *self = Chip8::new();
+    }
+
+    pub fn quirks_mode(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn set_quirks_mode(&mut self, quirks: Quirks) {
+        if quirks.mode == Chip8X && self.quirks.mode != Chip8X {
+            self.set_tone(chip8x::DEFAULT_TONE);
+        }
+        // HIRES CHIP-8 switches on its own, see HIRES_SWITCH
+        if quirks.mode != self.quirks.mode {
+            self.tall_mode = quirks.mode == TwoPage;
+        }
+        self.memory.resize(quirks.memory_allocated(), 0);
+        self.stack.resize(quirks.stack_allocated(), 0);
+        self.sp = self.sp.min(self.stack.len() as u16);
+        let font_changed = quirks.font != self.quirks.font;
+        self.quirks = quirks;
+        if font_changed {
+            self.load_font();
+        }
+    }
+
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+
+    pub fn set_core_mode(&mut self, mode: &str) -> Result<(), CoreError> {
+        let Some(mode) = Mode::from_name(mode) else {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidConfig(format!("unknown core mode '{}'", mode)),
+            ));
+        };
+        self.set_quirks_mode(Quirks::new(mode));
+        Ok(())
+    }
+
+    fn load_font(&mut self) {
+        let font = match &self.custom_font {
+            Some(font) => font.clone(),
+            None => self.quirks.font.data(),
+        };
+        self.memory[types::FONT_OFFSET..types::FONT_OFFSET + font::FONT_SIZE]
+            .copy_from_slice(&font);
+    }
+
+    // A font file (see font::parse_custom) to use instead of the quirks' font, None to go back
+    pub fn set_custom_font(&mut self, bytes: Option<&[u8]>) -> Result<(), CoreError> {
+        self.custom_font = match bytes {
+            Some(bytes) => Some(
+                font::parse_custom(bytes)
+                    .map_err(|e| CoreError::new(err_info!(), InvalidConfig(e)))?,
+            ),
+            None => None,
+        };
+        self.load_font();
+        Ok(())
+    }
+
+    pub fn load_rom(&mut self, bytes: Vec<u8>, start_offset: u16) -> Result<usize, CoreError> {
+        let start_offset = start_offset as usize;
+        let limit = match self.quirks.mode {
+            CosmacVip | Chip8X => VIP_RESERVED_START,
+            _ => self.quirks.memory_size,
+        };
+        let too_big = |limit: usize| {
+            InvalidRom(format!(
+                "Rom byte size {} + start_offset > {:#X}",
+                bytes.len(),
+                limit
+            ))
+        };
+        if bytes.len() + start_offset > limit {
+            self.platform_violation(err_info!(), too_big(limit))?;
+        }
+        if bytes.len() + start_offset > self.memory.len() {
+            return Err(CoreError::new(err_info!(), too_big(self.memory.len())));
+        }
+        for (i, v) in bytes.iter().enumerate() {
+            self.memory[i + start_offset] = *v;
+        }
+        // execution starts where the program is loaded, see Mode::program_start()
+        self.pc = start_offset as u16;
+        Ok(bytes.len())
+    }
+
+    pub fn get_screen(&self) -> Arc<Mutex<types::Screen>> {
+        Arc::clone(&self.screen)
+    }
+
+    // Should be called at a rate of 60Hz
+    pub fn tick_timers(&mut self) -> (u8, u8) {
+        // the VIP beeps while the key ending a Fx0A wait is held
+        let st = match self.input_key {
+            Some(_) => self.st.max(1),
+            None => self.st,
+        };
+        let dt = self.dt;
+        if self.st > 0 {
+            self.st -= 1;
+        }
+        if self.dt > 0 {
+            self.dt -= 1
+        }
+        (st, dt)
+    }
+
+    pub fn get_sound(&mut self) -> Option<&Sound> {
+        match self.sound.dirty {
+            true => {
+                self.sound.dirty = false;
+                Some(&self.sound)
+            }
+            false => None,
+        }
+    }
+
+    pub fn v_blank(&mut self) {
+        self.waiting_for_vblank = false;
+    }
+
+    /*
+       `count` instructions starting at `addr` as "0x0200: CLS" lines, invalid ones marked
+       with '?'. With symbols loaded lines read "0x02AA draw_player+0x6: CALL update"
+    */
+    pub fn disassemble(&self, addr: u16, count: usize) -> Vec<String> {
+        let mut lines = vec![];
+        let mut addr = addr;
+        for _ in 0..count {
+            let Some(instruction) = disasm::decode_at(&self.memory, addr, self.quirks.mode) else {
+                break;
+            };
+            let mark = if instruction.valid_in(self.quirks.mode) {
+                ""
+            } else {
+                " ?"
+            };
+            let location = match self.symbols.describe(addr) {
+                Some(name) => format!("{:#06X} {}", addr, name),
+                None => format!("{:#06X}", addr),
+            };
+            lines.push(format!(
+                "{}: {}{}",
+                location,
+                instruction.symbolic(&self.symbols),
+                mark
+            ));
+            addr = addr.wrapping_add(instruction.size());
+        }
+        lines
+    }
+
+    pub fn get_state(&self) -> String {
+        let mut s = format!(
+            "{}\nPC: {:#X}\nSP: {:#X}\nI: {:#X}\nDT: {:#X}\nST: {:#X}",
+            self.disassemble(self.pc, 4).join("\n"),
+            self.pc,
+            self.sp,
+            self.i,
+            self.dt,
+            self.st
+        );
+
+        let registers = self
+            .v
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        s = format!("{}\nV: [{}]", s, registers);
+
+        let stack = self
+            .stack
+            .iter()
+            .map(|&x| match self.symbols.describe(x) {
+                Some(name) => name,
+                None => x.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        s = format!("{}\nStack: [{}]", s, stack);
+
+        let keyboard = self
+            .keyboard
+            .iter()
+            .map(|&x| match x {
+                true => "X",
+                false => "-",
+            })
+            .collect::<Vec<&str>>()
+            .join(" ");
+        s = format!("{}\nKeys: [{}]", s, keyboard);
+
+        s = format!("{}\nhalt_for_input: {:?}", s, self.halted_for_input);
+        s = format!("{}\nexited: {:?}", s, self.exited);
+        s = format!("{}\nhires: {:?}", s, self.hires_mode);
+        s = format!("{}\ntall: {:?}", s, self.tall_mode);
+        s = format!(
+            "{}\nbit_plane_select: 0b{:04b} ({:?})",
+            s, self.bit_plane_selector, self.bit_plane_selector
+        );
+
+        s
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn i(&self) -> u32 {
+        self.i
+    }
+
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    pub fn v(&self) -> &[u8] {
+        &self.v
+    }
+
+    // Return addresses of the active call frames, outermost first
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn opcode_at(&self, addr: u16) -> Option<u16> {
+        let addr = addr as usize;
+        if addr + 1 >= self.memory.len() {
+            return None;
+        }
+        Some((self.memory[addr] as u16) << 8 | self.memory[addr + 1] as u16)
+    }
+
+    // Log the data memory accesses of each step(), see memory_accesses()
+    pub fn set_access_tracking(&mut self, enabled: bool) {
+        self.access_log.set_enabled(enabled);
+    }
+
+    // Data memory accessed by the last step(), empty unless access tracking is enabled
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        self.access_log.accesses()
+    }
+
+    // Instructions executed since reset, the cycle column of traces
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Names for addresses in disassembly, get_state() and traces
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
+
+    // Platform violations recorded since the last call, see PlatformCheck
+    pub fn take_warnings(&mut self) -> Vec<CoreError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn rpl_flags(&self) -> &[u8] {
+        &self.rpl
+    }
+
+    // Flags kept from an earlier run, extra bytes are ignored
+    pub fn set_rpl_flags(&mut self, flags: &[u8]) {
+        let len = flags.len().min(RPL_FLAGS);
+        self.rpl[..len].copy_from_slice(&flags[..len]);
+    }
+
+    // Whether Fx75 stored to the flags since the last call, ie. they need saving
+    pub fn take_rpl_changed(&mut self) -> bool {
+        std::mem::take(&mut self.rpl_changed)
+    }
+
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    // Colour attributes, only in CHIP-8X mode
+    pub fn color_board(&self) -> Option<&ColorBoard> {
+        match self.quirks.mode {
+            Chip8X => Some(&self.color_board),
+            _ => None,
+        }
+    }
+
+    // CHIP-8X second keypad, read by ExF2/ExF5
+    pub fn set_key2_state(&mut self, key: types::Key, is_pressed: bool) {
+        self.keyboard2[key as usize] = is_pressed;
+    }
+
+    // A byte arriving on the CHIP-8X input port, FxFB waits for it
+    pub fn set_port_input(&mut self, value: u8) {
+        self.port_in = Some(value);
+    }
+
+    // The MEGA-CHIP display while it's switched on (0011), None otherwise
+    pub fn mega_screen(&self) -> Option<&MegaScreen> {
+        match self.mega.enabled {
+            true => Some(&self.mega),
+            false => None,
+        }
+    }
+
+    // A MEGA-CHIP sample started or stopped since the last call
+    pub fn take_sample_event(&mut self) -> Option<SampleEvent> {
+        self.sample_event.take()
+    }
+
+    fn set_tone(&mut self, value: u8) {
+        self.sound.pitch = chip8x::tone_pitch(value);
+        self.sound.pattern = chip8x::TONE_PATTERN.to_vec();
+        self.sound.dirty = true;
+    }
+
+    // Display size in pixels: 64x32 lores, 128x64 hires and 64x64 tall
+    pub fn resolution(&self) -> (usize, usize) {
+        match (self.hires_mode, self.tall_mode) {
+            (true, _) => (DISPLAY_COLS, DISPLAY_ROWS),
+            (false, true) => (DISPLAY_COLS / 2, DISPLAY_ROWS),
+            (false, false) => (DISPLAY_COLS / 2, DISPLAY_ROWS / 2),
+        }
+    }
+
+    // True while step() is waiting on a key press (Fx0A) or the next v_blank (display wait)
+    pub fn is_blocked(&self) -> bool {
+        self.halted_for_input || self.waiting_for_vblank
+    }
+
+    pub fn set_key_state(&mut self, key: types::Key, is_pressed: bool) {
+        let cur_state = &mut self.keyboard[key as usize];
+
+        if self.halted_for_input && self.quirks.key_release_tone {
+            // Only a key going down during the wait counts, and it has to be released again
+            if is_pressed && !*cur_state && self.input_key.is_none() {
+                self.input_key = Some(key as u8);
+            }
+            if !is_pressed && *cur_state && self.input_key == Some(key as u8) {
+                self.v[self.halt_input_register as usize] = key as u8;
+                self.halted_for_input = false;
+                self.input_key = None;
+            }
+        } else if self.halted_for_input && *cur_state && !is_pressed {
+            self.v[self.halt_input_register as usize] = key as u8;
+            self.halted_for_input = false;
+        }
+        *cur_state = is_pressed;
+    }
+
+    #[inline]
+    fn fetch_opcode(&self) -> Result<u16, CoreError> {
+        self.opcode_at(self.pc).ok_or(CoreError::new(
+            err_info!(),
+            InvalidMemoryAccess(self.pc, self.pc as usize),
+        ))
+    }
+
+    #[inline]
+    fn skip_opcode(&mut self) {
+        // XO-Chip support: skip ahead 2 opcodes if the double-width opcode 0xF000 is next,
+        // the same for MEGA-CHIP's 01nn nnnn
+        let long = match self.opcode_at(self.pc) {
+            Some(0xF000) => true,
+            Some(opcode) => self.quirks.mode == MegaChip && opcode & 0xFF00 == 0x0100,
+            None => false,
+        };
+        // pc wraps at the top of 64 KiB like the address bus
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    pub fn step(&mut self) -> Result<StepResult, CoreError> {
+        if self.exited {
+            return Ok(StepResult::Exited);
+        }
+        if self.halted_for_input {
+            return Ok(StepResult::Ran(1));
+        }
+        if self.waiting_for_vblank {
+            return Ok(StepResult::Ran(1));
+        }
+        self.access_log.clear();
+        self.cycles += 1;
+
+        // step() returns what the instruction cost, see Timing
+        let pc = self.pc;
+        let opcode = self.opcode_at(pc).unwrap_or(0);
+        let vx = self.v[get_x!(opcode)];
+        let v = match &self.tracer {
+            Some(tracer) if tracer.wants(pc) => self.v.clone(),
+            _ => return self.execute().map(|_| self.step_result(pc, opcode, vx)),
+        };
+        let result = self.execute().map(|_| self.step_result(pc, opcode, vx));
+
+        let changed = (0..v.len())
+            .filter(|x| v[*x] != self.v[*x])
+            .map(|x| (x, self.v[x]))
+            .collect();
+        let instruction = match disasm::decode_at(&self.memory, pc, self.quirks.mode) {
+            Some(instruction) => instruction.symbolic(&self.symbols),
+            None => String::new(),
+        };
+        let entry = TraceEntry {
+            cycle: self.cycles,
+            pc,
+            opcode,
+            instruction,
+            i: self.i,
+            vf: self.v[0xF],
+            changed,
+            location: self.symbols.describe(pc),
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(entry);
+        }
+        result
+    }
+
+    fn step_result(&self, pc: u16, opcode: u16, vx: u8) -> StepResult {
+        if self.exited {
+            return StepResult::Exited;
+        }
+        let outcome = Outcome {
+            vx,
+            skipped: self.pc > pc.wrapping_add(2),
+            collided: opcode & 0xF000 == 0xD000 && self.v[0xF] != 0,
+        };
+        StepResult::Ran(self.quirks.timing.cost(opcode, outcome))
+    }
+
+    fn execute(&mut self) -> Result<i32, CoreError> {
+        let opcode = self.fetch_opcode()?;
+        if self.pc as usize + 2 > self.quirks.memory_size {
+            self.platform_violation(
+                err_info!(),
+                InvalidMemoryAccess(self.pc, self.pc as usize),
+            )?;
+        }
+        // Opcodes added by later platforms, unknown ones fail below
+        if let Some(since) = disasm::platform_of(opcode, self.quirks.mode) {
+            if !Platform::of_mode(self.quirks.mode).includes(since) {
+                self.platform_violation(
+                    err_info!(),
+                    UnsupportedInModeOpcode(self.pc, opcode, self.quirks.mode_label.clone()),
+                )?;
+            }
+        }
+        self.pc = self.pc.wrapping_add(2);
+
+        match opcode & 0xF000 {
+            0x0000 => {
+                match get_nnn!(opcode) {
+                    0x02A0 if self.quirks.mode == Chip8X => {
+                        // CHIP-8X: (02A0) Step the background colour
+                        self.color_board.step_background();
+                    }
+                    0x0230 if self.quirks.mode == HiresChip8 => {
+                        // HIRES CHIP-8: (0230) Clear the 64x64 display
+                        for layer in 0..DISPLAY_LAYERS {
+                            self.clear_layer(layer);
+                        }
+                    }
+                    0x0010 if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (0010) Back to the SuperChip display
+                        self.mega.enabled = false;
+                    }
+                    0x0011 if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (0011) Switch to the 256x192 colour display
+                        self.mega.enabled = true;
+                    }
+                    0x0100..=0x01FF if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (01nn nnnn) Load the 24 bit address nnnnnn into I
+                        let low = self.fetch_opcode()?;
+                        self.i = (get_kk!(opcode) as u32) << 16 | low as u32;
+                        self.pc = self.pc.wrapping_add(2);
+                    }
+                    0x0200..=0x02FF if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (02nn) Load nn ARGB colours from I into the palette
+                        let len = get_kk!(opcode) as usize * 4;
+                        let i = self.i as usize;
+                        if i + len > self.memory.len() {
+                            return Err(CoreError::new(
+                                err_info!(),
+                                InvalidMemoryAccess(self.pc, i + len - 1),
+                            ));
+                        }
+                        self.record_access(i, len, AccessKind::Read)?;
+                        self.mega.load_palette(&self.memory[i..i + len]);
+                    }
+                    0x0300..=0x03FF if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (03nn) Sprite width, 0 is 256
+                        self.mega.sprite_width = get_kk!(opcode) as usize;
+                    }
+                    0x0400..=0x04FF if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (04nn) Sprite height, 0 is 256
+                        self.mega.sprite_height = get_kk!(opcode) as usize;
+                    }
+                    0x0500..=0x05FF if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (05nn) Opacity of the display
+                        self.mega.alpha = get_kk!(opcode);
+                    }
+                    0x0600..=0x060F if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (060n) Play the sample at I, looping when n is 0
+                        let i = self.i as usize;
+                        let Some(sample) = self
+                            .memory
+                            .get(i..)
+                            .and_then(|data| megachip::read_sample(data, get_n!(opcode) == 0))
+                        else {
+                            return Err(CoreError::new(
+                                err_info!(),
+                                InvalidMemoryAccess(self.pc, i + megachip::SAMPLE_HEADER - 1),
+                            ));
+                        };
+                        let len = megachip::SAMPLE_HEADER + sample.data.len();
+                        self.record_access(i, len, AccessKind::Read)?;
+                        self.sample_event = Some(SampleEvent::Play(sample));
+                    }
+                    0x0700 if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (0700) Stop the sample
+                        self.sample_event = Some(SampleEvent::Stop);
+                    }
+                    0x0800..=0x0805 if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (080n) Blend mode, see megachip::Blend
+                        self.mega.blend = megachip::Blend::from_id(get_n!(opcode) as u8)
+                            .unwrap_or(megachip::Blend::Normal);
+                    }
+                    0x0900..=0x09FF if self.quirks.mode == MegaChip => {
+                        // MEGA-CHIP: (09nn) Palette index that sets VF when drawn over
+                        self.mega.collision_color = get_kk!(opcode);
+                    }
+                    0x00B0..=0x00BF if self.mega.enabled => {
+                        // MEGA-CHIP: (00BN) Scroll display N lines up
+                        self.mega.scroll(0, -(get_n!(opcode) as isize));
+                    }
+                    0x00C0..=0x00CF if self.mega.enabled => {
+                        // MEGA-CHIP: scrolling moves the colour display instead
+                        self.mega.scroll(0, get_n!(opcode) as isize);
+                    }
+                    0x00C0..=0x00CF => {
+                        // Note: technically, 00C0 causes a crash on hardware
+                        //       but xo-chip treats it as a nop... so I'm leaving it
+
+                        // (00CN)*    Scroll display N lines down
+                        let n = get_n!(opcode) as usize;
+                        let mut scroll_distance = n;
+
+                        // SuperChip 'modern' low-res scrolling requires doubling
+                        // See: https://github.com/Timendus/chip8-test-suite/blob/main/legacy-superchip.md#how-a-design-flaw-morphed-over-time
+                        if !self.hires_mode && !self.quirks.lores_scroll_half {
+                            scroll_distance *= DISPLAY_ROWS / self.resolution().1;
+                        }
+
+                        for layer in 0..DISPLAY_LAYERS {
+                            if (self.bit_plane_selector >> layer) & 0b1 == 1 {
+                                self.scroll_plane_down(scroll_distance, layer)?;
+                            }
+                        }
+                    }
+                    0x00B0..=0x00BF | 0x00D0..=0x00DF
+                        if opcode & 0x00F0 == 0x00D0 || self.quirks.mode == MegaChip =>
+                    {
+                        // XO-CHIP: (00DN) Scroll display N lines up
+                        // MEGA-CHIP: (00BN) the same
+
+                        let n = get_n!(opcode) as usize;
+                        let mut scroll_distance = n;
+
+                        // SuperChip 'modern' low-res scrolling requires doubling
+                        // See: https://github.com/Timendus/chip8-test-suite/blob/main/legacy-superchip.md#how-a-design-flaw-morphed-over-time
+                        if !self.hires_mode && !self.quirks.lores_scroll_half {
+                            scroll_distance *= DISPLAY_ROWS / self.resolution().1;
+                        }
+
+                        for layer in 0..DISPLAY_LAYERS {
+                            if (self.bit_plane_selector >> layer) & 0b1 == 1 {
+                                self.scroll_plane_up(scroll_distance, layer)?;
+                            }
+                        }
+                    }
+                    0x00E0 if self.mega.enabled => {
+                        // MEGA-CHIP: show the frame drawn since the last 00E0 and start a new one
+                        self.mega.present();
+                    }
+                    0x00E0 => {
+                        // CLS
+                        for layer in 0..DISPLAY_LAYERS {
+                            if (self.bit_plane_selector >> layer) & 0b1 == 1 {
+                                self.clear_layer(layer);
+                            }
+                        }
+                    }
+                    0x00EE => {
+                        // RET
+                        if self.sp == 0 {
+                            return Err(CoreError::new(
+                                err_info!(),
+                                StackOverflow(self.pc, self.sp),
+                            ));
+                        }
+                        self.sp -= 1;
+                        self.pc = self.stack[self.sp as usize];
+                    }
+                    0x00FB if self.mega.enabled => self.mega.scroll(4, 0),
+                    0x00FC if self.mega.enabled => self.mega.scroll(-4, 0),
+                    0x00FB => {
+                        // 00FB*    Scroll display 4 pixels right
+
+                        for layer in 0..DISPLAY_LAYERS {
+                            if (self.bit_plane_selector >> layer) & 0b1 == 1 {
+                                self.scroll_layer_right(layer);
+                            }
+                        }
+                    }
+                    0x00FC => {
+                        // 00FC*    Scroll display 4 pixels left
+
+                        for layer in 0..DISPLAY_LAYERS {
+                            if (self.bit_plane_selector >> layer) & 0b1 == 1 {
+                                self.scroll_layer_left(layer);
+                            }
+                        }
+                    }
+                    0x00FD => {
+                        // 00FD*    Exit CHIP interpreter
+                        self.pc -= 2;
+                        self.exited = true;
+                    }
+                    0x00FE => {
+                        // 00FE*    Disable extended screen mode
+                        self.hires_mode = false;
+                        if self.quirks.resolution_clear {
+                            for layer in 0..DISPLAY_LAYERS {
+                                self.clear_layer(layer);
+                            }
+                        }
+                    }
+                    0x00FF => {
+                        // 00FF*    Enable extended screen mode
+                        self.hires_mode = true;
+                        if self.quirks.resolution_clear {
+                            for layer in 0..DISPLAY_LAYERS {
+                                self.clear_layer(layer);
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(CoreError::new(err_info!(), InvalidOpcode(self.pc, opcode)));
+                    }
+                }
+            }
+            0x1000 if self.quirks.mode == HiresChip8 && opcode == HIRES_SWITCH && self.pc == 0x202 => {
+                // HIRES CHIP-8: (1260) at 0x200 switches to 64x64 and starts the program
+                self.tall_mode = true;
+                self.pc = HIRES_ENTRY;
+            }
+            0x1000 => {
+                // (1nnn) JMP addr
+                self.pc = get_nnn!(opcode);
+            }
+            0x2000 => {
+                // (2nnn) CALL addr
+                if self.sp as usize >= self.quirks.stack_depth {
+                    self.platform_violation(err_info!(), StackOverflow(self.pc, self.sp))?;
+                }
+                if self.sp as usize >= self.stack.len() {
+                    return Err(CoreError::new(err_info!(), StackOverflow(self.pc, self.sp)));
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = get_nnn!(opcode);
+            }
+            0x3000 => {
+                // (3xkk) SE Vx, byte - skip if equal
+                if self.v[get_x!(opcode)] == get_kk!(opcode) {
+                    self.skip_opcode();
+                }
+            }
+            0x4000 => {
+                // (4xkk) SNE Vx, byte - skip if not equal
+                if self.v[get_x!(opcode)] != get_kk!(opcode) {
+                    self.skip_opcode()
+                }
+            }
+            0x5000 => {
+                match get_n!(opcode) {
+                    0x0 => {
+                        // (5xy0) - SE Vx, Vy - skip if registers are equal
+                        if self.v[get_x!(opcode)] == self.v[get_y!(opcode)] {
+                            self.skip_opcode();
+                        }
+                    }
+                    0x1 => {
+                        // CHIP-8X: (5xy1) - Add each nibble of Vy to Vx, modulo 8
+                        let x = get_x!(opcode);
+                        self.v[x] = chip8x::add_nibbles(self.v[x], self.v[get_y!(opcode)]);
+                    }
+                    0x2 => {
+                        // XO-CHIP: (5xy2) - write registers vX to vY to memory pointed to by I
+                        let x = get_x!(opcode);
+                        let y = get_y!(opcode);
+                        let dist = x.abs_diff(y);
+
+                        if self.i as usize + dist > self.memory.len() - 1 {
+                            return Err(CoreError::new(
+                                err_info!(),
+                                InvalidMemoryAccess(self.pc, self.i as usize + dist),
+                            ));
+                        }
+                        self.record_access(self.i as usize, dist + 1, AccessKind::Write)?;
+                        if x < y {
+                            for z in 0..dist + 1 {
+                                self.memory[self.i as usize + z] = self.v[x + z];
+                            }
+                        } else {
+                            for z in 0..dist + 1 {
+                                self.memory[self.i as usize + z] = self.v[x - z];
+                            }
+                        }
+                    }
+                    0x3 => {
+                        // XO-CHIP: (5xy3) - load registers vX to vY from memory pointed to by I
+                        let x = get_x!(opcode);
+                        let y = get_y!(opcode);
+                        let dist = x.abs_diff(y);
+                        if self.i as usize + dist > self.memory.len() - 1 {
+                            return Err(CoreError::new(
+                                err_info!(),
+                                InvalidMemoryAccess(self.pc, self.i as usize + dist),
+                            ));
+                        }
+                        self.record_access(self.i as usize, dist + 1, AccessKind::Read)?;
+                        if x < y {
+                            for z in 0..dist + 1 {
+                                self.v[x + z] = self.memory[self.i as usize + z];
+                            }
+                        } else {
+                            for z in 0..dist + 1 {
+                                self.v[x - z] = self.memory[self.i as usize + z];
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(CoreError::new(err_info!(), InvalidOpcode(self.pc, opcode)));
+                    }
+                }
+            }
+            0x6000 => {
+                // (6xkk) - LD Vx, byte - Load byte into register
+                self.v[get_x!(opcode)] = get_kk!(opcode);
+            }
+            0x7000 => {
+                // (7xkk) Add Vx, byte - Add byte to register
+                let v_x = &mut self.v[get_x!(opcode)];
+                let (s, _) = v_x.overflowing_add(get_kk!(opcode));
+                *v_x = s;
+            }
+            0x8000 => {
+                //
+                match get_n!(opcode) {
+                    0x0 => {
+                        // (8xy0) - LD Vx, Vy - move V_y into V_x
+                        self.v[get_x!(opcode)] = self.v[get_y!(opcode)];
+                    }
+                    0x1 => {
+                        // (8xy1) - OR Vx, Vy - Compute V_x |= V_y
+                        self.v[get_x!(opcode)] |= self.v[get_y!(opcode)];
+                        // reset Vf for Chip, not SCHIP
+                        if self.quirks.vf_reset {
+                            self.v[0xF] = 0;
+                        }
+                    }
+                    0x2 => {
+                        // (8xy2) - AND Vx, Vy - Compute V_x &= V_y
+                        self.v[get_x!(opcode)] &= self.v[get_y!(opcode)];
+                        if self.quirks.vf_reset {
+                            self.v[0xF] = 0;
+                        }
+                    }
+                    0x3 => {
+                        // (8xy3) - XOR Vx, Vy - Compute V_x ^= V_y
+                        self.v[get_x!(opcode)] ^= self.v[get_y!(opcode)];
+                        if self.quirks.vf_reset {
+                            self.v[0xF] = 0;
+                        }
+                    }
+                    0x4 => {
+                        // 8xy4 - ADD Vx, Vy - Compute V_x += V_y, set overflow
+                        let x = get_x!(opcode);
+                        let y = get_y!(opcode);
+                        let (s, overflow) = self.v[x].overflowing_add(self.v[y]);
+                        self.v[x] = s;
+                        self.v[0xF] = match overflow {
+                            true => 1,
+                            false => 0,
+                        };
+                    }
+                    0x5 => {
+                        // (8xy5) - SUB Vx, Vy - Compute V_x -= V_y, set underflow
+                        let x = get_x!(opcode);
+                        let y = get_y!(opcode);
+                        let (s, underflow) = self.v[x].overflowing_sub(self.v[y]);
+                        self.v[x] = s;
+                        self.v[0xF] = match underflow {
+                            true => 0,
+                            false => 1,
+                        };
+                    }
+                    0x6 => {
+                        // (8xy6) - SHR Vx - Compute V_x >>= 1, store least-sig bit in VF
+                        let x = get_x!(opcode);
+                        if !self.quirks.shifting_vx {
+                            let y = get_y!(opcode);
+                            self.v[x] = self.v[y];
+                        }
+                        let v_x = self.v[x];
+                        self.v[x] = v_x >> 1;
+                        self.v[0xF] = v_x & 0x1;
+                    }
+                    0x7 => {
+                        // (8xy7) - SUBN Vx, Vy - Compute V_x = V_y - V_x, set borrow-flag in VF
+                        let x = get_x!(opcode);
+                        let y = get_y!(opcode);
+                        let (s, borrow) = self.v[y].overflowing_sub(self.v[x]);
+                        self.v[x] = s;
+                        self.v[0xF] = match borrow {
+                            true => 0,
+                            false => 1,
+                        };
+                    }
+                    0xE => {
+                        // (8xyE) - SHL Vx - Computer V_x <<= 1,
+                        let x = get_x!(opcode);
+                        if !self.quirks.shifting_vx {
+                            let y = get_y!(opcode);
+                            self.v[x] = self.v[y];
+                        }
+                        let v_x = self.v[x];
+                        self.v[x] = v_x << 1;
+                        self.v[0xF] = (v_x >> 7) & 0x1;
+                    }
+                    _ => {
+                        return Err(CoreError::new(err_info!(), InvalidOpcode(self.pc, opcode)));
+                    }
+                }
+            }
+            0x9000 => {
+                // (9xy0) - COND - skip if V_x != V_y
+                match get_n!(opcode) {
+                    0x0 => {
+                        if self.v[get_x!(opcode)] != self.v[get_y!(opcode)] {
+                            self.skip_opcode();
+                        }
+                    }
+                    _ => {
+                        return Err(CoreError::new(err_info!(), InvalidOpcode(self.pc, opcode)));
+                    }
+                }
+            }
+            0xA000 => {
+                // (Annn) - LDI - Load nnn into I
+                self.i = get_nnn!(opcode) as u32;
+            }
+            0xB000 if self.quirks.mode == Chip8X => {
+                // CHIP-8X: (Bxy0) - Colour the zone blocks given by Vx and Vx+1 with Vy
+                //          (Bxyn) - Colour n zone rows at Vx, Vx+1 with Vy
+                let x = get_x!(opcode);
+                let h = self.v[x];
+                let v = self.v[(x + 1) & 0xF];
+                let color = self.v[get_y!(opcode)];
+                match get_n!(opcode) {
+                    0 => self.color_board.fill_blocks(h, v, color),
+                    n => self.color_board.fill_rows(h, v, n as u8, color),
+                }
+            }
+            0xB000 => {
+                // (Bnnn) - JP V0, addr - Jump to V0 (or Vx) + addr
+                if self.quirks.jump_plus_vx {
+                    self.pc = self.v[get_x!(opcode)] as u16 + get_nnn!(opcode);
+                } else {
+                    self.pc = self.v[0] as u16 + get_nnn!(opcode);
+                }
+            }
+            0xC000 => {
+                // (Cxkk) - RND Vx, byte - Bitwise and kk with random number [0,255]
+                self.v[get_x!(opcode)] = self.rng.next_byte() & get_kk!(opcode);
+            }
+            0xD000 => {
+                // (Dxyn) - DRW Vx, Vy, nibble
+                let col = self.v[get_x!(opcode)];
+                let row = self.v[get_y!(opcode)];
+                let n = get_n!(opcode) as u8;
+
+                self.v[0xF] = 0;
+                if self.mega.enabled {
+                    self.v[0xF] = self.draw_mega_sprite(col, row, n)? as u8;
+                    if self.quirks.display_wait {
+                        self.waiting_for_vblank = true;
+                    }
+                    return Ok(1);
+                }
+                let mut page_num = 0;
+                for layer in 0..DISPLAY_LAYERS {
+                    if (self.bit_plane_selector >> layer) & 0b1 == 1 {
+                        self.draw_sprite(col, row, n, page_num, layer)?;
+                        page_num += 1;
+                    }
+                }
+
+                // the HP-48 only waits for the display in lores
+                if self.quirks.display_wait && !(self.hires_mode && self.quirks.mode.is_hp48()) {
+                    self.waiting_for_vblank = true;
+                }
+            }
+            0xE000 => {
+                // User inputs
+                match get_kk!(opcode) {
+                    0x9E => {
+                        // (Ex9E) - SKP Vx - Skip if V_x is pressed
+                        let x = get_x!(opcode);
+                        if self.keyboard[(self.v[x] & 0xF) as usize] {
+                            self.skip_opcode();
+                        }
+                    }
+                    0xA1 => {
+                        // (ExA1) - SKNP Vx - Skip if V_x isn't pressed
+                        let x = get_x!(opcode);
+                        if !self.keyboard[(self.v[x] & 0xF) as usize] {
+                            self.skip_opcode();
+                        }
+                    }
+                    0xF2 => {
+                        // CHIP-8X: (ExF2) - Skip if V_x is pressed on the second keypad
+                        let x = get_x!(opcode);
+                        if self.keyboard2[(self.v[x] & 0xF) as usize] {
+                            self.skip_opcode();
+                        }
+                    }
+                    0xF5 => {
+                        // CHIP-8X: (ExF5) - Skip if V_x isn't pressed on the second keypad
+                        let x = get_x!(opcode);
+                        if !self.keyboard2[(self.v[x] & 0xF) as usize] {
+                            self.skip_opcode();
+                        }
+                    }
+                    _ => {
+                        return Err(CoreError::new(err_info!(), InvalidOpcode(self.pc, opcode)));
+                    }
+                }
+            }
+            0xF000 => {
+                match get_nnn!(opcode) {
+                    0x000 => {
+                        // XO-CHIP Support: (0xF000) - assign next 16 bit word to i
+                        self.i = self.fetch_opcode()? as u32;
+                        self.pc = self.pc.wrapping_add(2);
+                    }
+                    0x002 => {
+                        // XO-CHIP Support: (0xF002) - load 16 bytes audio pattern pointed to by I into audio pattern buffer
+                        if self.i as usize + 15 > self.memory.len() - 1 {
+                            return Err(CoreError::new(
+                                err_info!(),
+                                InvalidMemoryAccess(self.pc, self.i as usize + 15),
+                            ));
+                        }
+                        self.record_access(self.i as usize, 16, AccessKind::Read)?;
+                        for offset in 0..16 {
+                            self.sound.pattern[offset] = self.memory[self.i as usize + offset];
+                        }
+                        self.sound.dirty = true;
+                    }
+                    _ => {
+                        // Misc (Fx--)
+                        match get_kk!(opcode) {
+                            0x01 => {
+                                // XO-Chip Support: (0xFX01) - select bit planes to draw on when drawing with Dxy0/Dxyn
+                                self.bit_plane_selector = get_x!(opcode) as u8;
+                            }
+                            0x07 => {
+                                // (Fx07) - LD Vx, DT
+                                self.v[get_x!(opcode)] = self.dt;
+                            }
+                            0x0A => {
+                                // (Fx0A) - LD Vx, K - Halt for input, then store it in Vx
+                                let x = get_x!(opcode);
+                                self.halt_input_register = x as u8;
+                                self.halted_for_input = true;
+                            }
+                            0x15 => {
+                                // (Fx15) - LD DT, Vx
+                                self.dt = self.v[get_x!(opcode)];
+                            }
+                            0x18 => {
+                                // (Fx18) - LD ST, Vx
+                                self.st = self.v[get_x!(opcode)];
+                            }
+                            0x1E => {
+                                // (Fx1E) - ADD I, Vx
+                                // I is only checked against memory when it's used
+                                let v_x = self.v[get_x!(opcode)] as u32;
+                                self.i = self.i.wrapping_add(v_x);
+                                if self.quirks.fx1e_overflow {
+                                    // the Amiga interpreter keeps I within 12 bits
+                                    self.v[0xF] = (self.i > 0x0FFF) as u8;
+                                    self.i &= 0x0FFF;
+                                }
+                            }
+                            0x29 => {
+                                // (Fx29) - LD F, Vx
+                                self.i = font::small_glyph(self.v[get_x!(opcode)]) as u32;
+                            }
+                            0x30 => {
+                                // FX30*    Point I to 10-byte font sprite for digit VX (0..9)
+                                self.i = font::big_glyph(self.v[get_x!(opcode)]) as u32;
+                            }
+                            0x33 => {
+                                // (Fx33) - LD B, Vx
+                                let v_x = self.v[get_x!(opcode)];
+                                let i_usize = self.i as usize;
+                                if i_usize + 2 > self.memory.len() - 1 {
+                                    return Err(CoreError::new(
+                                        err_info!(),
+                                        InvalidMemoryPtr(self.pc, i_usize),
+                                    ));
+                                }
+                                self.record_access(i_usize, 3, AccessKind::Write)?;
+                                self.memory[i_usize] = (v_x as u16 / 100) as u8;
+                                self.memory[i_usize + 1] = (v_x % 100) / 10;
+                                self.memory[i_usize + 2] = v_x % 10;
+                            }
+                            0x3A => {
+                                // XO-CHIP Support: (0xFX3a) - set audio pitch
+                                let x = self.v[get_x!(opcode)];
+                                self.sound.pitch = x;
+                            }
+                            0x55 => {
+                                // (Fx55) - LD [I], Vx - Store V0..VX in memory starting at i
+                                let x = get_x!(opcode);
+                                if self.i as usize + x > self.memory.len() - 1 {
+                                    return Err(CoreError::new(
+                                        err_info!(),
+                                        InvalidMemoryPtr(self.pc, self.i as usize),
+                                    ));
+                                }
+                                self.record_access(self.i as usize, x + 1, AccessKind::Write)?;
+                                for i in 0..=x {
+                                    self.memory[self.i as usize + i] = self.v[i];
+                                }
+                                if self.quirks.load_store_index_increase {
+                                    self.i += x as u32 + 1;
+                                }
+                            }
+                            0x65 => {
+                                // (Fx65) - LD Vx, [I] - Load V0..VX in memory starting at i
+                                let x = get_x!(opcode);
+                                if self.i as usize + x > self.memory.len() - 1 {
+                                    return Err(CoreError::new(
+                                        err_info!(),
+                                        InvalidMemoryPtr(self.pc, self.i as usize),
+                                    ));
+                                }
+                                self.record_access(self.i as usize, x + 1, AccessKind::Read)?;
+                                for i in 0..=x {
+                                    self.v[i] = self.memory[self.i as usize + i];
+                                }
+                                if self.quirks.load_store_index_increase {
+                                    self.i += x as u32 + 1;
+                                }
+                            }
+                            0xF8 => {
+                                // CHIP-8X: (FxF8) - Output Vx to the port, the VP-595 tone
+                                self.set_tone(self.v[get_x!(opcode)]);
+                            }
+                            0xFB => {
+                                // CHIP-8X: (FxFB) - Wait for a byte on the input port, load it into Vx
+                                match self.port_in.take() {
+                                    Some(value) => self.v[get_x!(opcode)] = value,
+                                    None => self.pc -= 2,
+                                }
+                            }
+                            0x75 => {
+                                // FX75*    Store V0..VX in RPL user flags (X <= 7 on Super-Chip)
+                                let x = get_x!(opcode);
+                                self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+                                self.rpl_changed = true;
+                            }
+                            0x85 => {
+                                // FX85*    Read V0..VX from RPL user flags (X <= 7 on Super-Chip)
+                                let x = get_x!(opcode);
+                                self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+                            }
+                            _ => {
+                                return Err(CoreError::new(
+                                    err_info!(),
+                                    InvalidOpcode(self.pc, opcode),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(CoreError::new(err_info!(), InvalidOpcode(self.pc, opcode)));
+            }
+        }
+        Ok(1)
+    }
+
+    // Logs a data access and checks it against the platform's memory size
+    fn record_access(&mut self, addr: usize, len: usize, kind: AccessKind) -> Result<(), CoreError> {
+        self.access_log.record(addr, len, kind);
+        if addr + len > self.quirks.memory_size {
+            self.platform_violation(err_info!(), InvalidMemoryAccess(self.pc, addr + len - 1))?;
+        }
+        Ok(())
+    }
+
+    // A ROM stepped outside its platform: an error with Trap, a warning with Warn
+    fn platform_violation(&mut self, info: String, error_type: CoreErrorType) -> Result<(), CoreError> {
+        let error = CoreError::new(info, error_type);
+        if self.quirks.platform_check == PlatformCheck::Trap {
+            return Err(error);
+        }
+        // one warning per instruction, a loop would repeat it every frame
+        if self.warned.insert(error.error_type.pc().unwrap_or(self.pc)) {
+            self.warnings.push(error);
+        }
+        Ok(())
+    }
+
+    fn clear_layer(&mut self, layer: usize) {
+        let mut screen_writer = self.screen.lock().unwrap();
+        for row in screen_writer.iter_mut() {
+            for cell in row.iter_mut() {
+                (*cell)[layer] = false;
+            }
+        }
+    }
+
+    fn scroll_plane_up(&mut self, scroll_distance: usize, layer: usize) -> Result<(), CoreError> {
+        let mut screen_writer = self.screen.lock().unwrap();
+        if layer >= DISPLAY_LAYERS {
+            return Err(CoreError::new(err_info!(), InvalidLayer(layer)));
+        }
+        for r in 0..DISPLAY_ROWS - scroll_distance {
+            for c in 0..DISPLAY_COLS {
+                screen_writer[r][c][layer] = screen_writer[r + scroll_distance][c][layer];
+            }
+        }
+        for i in DISPLAY_ROWS - scroll_distance..DISPLAY_ROWS {
+            for c in 0..DISPLAY_COLS {
+                screen_writer[i][c][layer] = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn scroll_plane_down(&mut self, scroll_distance: usize, layer: usize) -> Result<(), CoreError> {
+        let mut screen_writer = self.screen.lock().unwrap();
+        if layer >= DISPLAY_LAYERS {
+            return Err(CoreError::new(err_info!(), InvalidLayer(layer)));
+        }
+        for r in (scroll_distance..DISPLAY_ROWS).rev() {
+            for c in 0..DISPLAY_COLS {
+                screen_writer[r][c][layer] = screen_writer[r - scroll_distance][c][layer];
+            }
+        }
+        for i in 0..scroll_distance {
+            for c in 0..DISPLAY_COLS {
+                screen_writer[i][c][layer] = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn scroll_layer_left(&mut self, layer: usize) {
+        let mut screen_writer = self.screen.lock().unwrap();
+
+        let mut scroll_distance = 4;
+        if !self.hires_mode && !self.quirks.lores_scroll_half {
+            scroll_distance *= 2;
+        }
+
+        for row in 0..DISPLAY_ROWS {
+            for c in scroll_distance..DISPLAY_COLS {
+                screen_writer[row][c - scroll_distance][layer] = screen_writer[row][c][layer];
+            }
+            for c in DISPLAY_COLS - scroll_distance..DISPLAY_COLS {
+                screen_writer[row][c][layer] = false;
+            }
+        }
+    }
+
+    fn scroll_layer_right(&mut self, layer: usize) {
+        // QUIRK: Scrolling in superchip lowres 'modern' (incorrectly) requires doubling.
+        //        In legacy, it doesn't
+        let mut scroll_distance = 4;
+        if !self.hires_mode && !self.quirks.lores_scroll_half {
+            scroll_distance *= 2;
+        }
+
+        let mut screen_writer = self.screen.lock().unwrap();
+        for row in 0..DISPLAY_ROWS {
+            for c in (scroll_distance..DISPLAY_COLS).rev() {
+                screen_writer[row][c][layer] = screen_writer[row][c - scroll_distance][layer];
+            }
+            for c in 0..scroll_distance {
+                screen_writer[row][c][layer] = false;
+            }
+        }
+    }
+
+    // Dxyn on the MEGA-CHIP display, true on a collision
+    fn draw_mega_sprite(&mut self, col: u8, row: u8, n: u8) -> Result<bool, CoreError> {
+        let (col, row) = (col as usize, row as usize);
+        let addr = self.i as usize;
+        let len = match addr < megachip::FONT_END {
+            true => n as usize,
+            false => {
+                let (width, height) = self.mega.sprite_size();
+                width * height
+            }
+        };
+        if addr + len > self.memory.len() {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidMemoryAccess(self.pc, addr + len - 1),
+            ));
+        }
+        self.record_access(addr, len, AccessKind::Read)?;
+        let sprite = &self.memory[addr..addr + len];
+        Ok(match addr < megachip::FONT_END {
+            true => self.mega.draw_font(col, row, sprite),
+            false => self.mega.draw_sprite(col, row, sprite),
+        })
+    }
+
+    fn draw_sprite(
+        &mut self,
+        col: u8,
+        row: u8,
+        sprite_rows: u8,
+        page_num: usize,
+        layer: usize,
+    ) -> Result<(), CoreError> {
+        let sprite_offset = self.i as usize;
+        // a SuperChip 16x16 sprite is 16 rows of 2 bytes, a Chip8 one is n rows of 1.
+        // SCHIP 1.0 draws Dxy0 in lores as 16 rows of 1, CHIP-8 draws no rows at all
+        let big_sprites = Platform::of_mode(self.quirks.mode).includes(Platform::SuperChip10);
+        let (width, height) = match sprite_rows {
+            0 if !big_sprites => (8, 0),
+            0 if !self.hires_mode && self.quirks.mode == SuperChip10 => (8, 16),
+            0 => (16, 16),
+            n => (8, n as usize),
+        };
+        let row_bytes = width / 8;
+        let sprite_size = row_bytes * height;
+        self.record_access(
+            sprite_offset + page_num * sprite_size,
+            sprite_size,
+            AccessKind::Read,
+        )?;
+        // the HP-48 sets VF to the rows that collided or fell off the bottom in hires
+        let count_rows = self.hires_mode && self.quirks.mode.is_hp48();
+        let mut hit_rows = 0;
+        let mut collided = false;
+        // each display pixel covers a block of the 128x64 screen
+        let (cols, rows) = self.resolution();
+        let (scale_x, scale_y) = (DISPLAY_COLS / cols, DISPLAY_ROWS / rows);
+
+        let mut screen_writer = self.screen.lock().unwrap();
+        for r in 0..height {
+            let mem_loc = sprite_offset + page_num * sprite_size + r * row_bytes;
+            if mem_loc + row_bytes > self.memory.len() {
+                return Err(CoreError::new(
+                    err_info!(),
+                    InvalidMemoryAccess(self.pc, mem_loc),
+                ));
+            }
+            let sprite_row = self.memory[mem_loc..mem_loc + row_bytes]
+                .iter()
+                .fold(0u16, |acc, b| acc << 8 | *b as u16);
+            let mut row_hit = false;
+            let mut row_clipped = false;
+            for c in 0..width {
+                let bit = (sprite_row >> (width - 1 - c)) & 0x1 == 1;
+                let screen_x = col as usize % cols + c;
+                let screen_y = row as usize % rows + r;
+                if self.quirks.clipping && (screen_x >= cols || screen_y >= rows) {
+                    row_clipped |= screen_y >= rows;
+                    continue;
+                }
+                for i in 0..scale_x {
+                    for j in 0..scale_y {
+                        let curr = &mut screen_writer[(screen_y % rows) * scale_y + j]
+                            [(screen_x % cols) * scale_x + i][layer];
+                        if bit && *curr {
+                            row_hit = true;
+                        }
+                        *curr ^= bit;
+                    }
+                }
+            }
+            if row_hit || row_clipped {
+                hit_rows += 1;
+            }
+            collided |= row_hit;
+        }
+        if count_rows {
+            self.v[0xF] = self.v[0xF].max(hit_rows);
+        } else if collided {
+            self.v[0xF] = 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mode: &str, rom: &[u8], steps: usize) -> Result<Chip8, CoreError> {
+        let mut chip = Chip8::new();
+        chip.set_core_mode(mode)?;
+        chip.load_rom(rom.to_vec(), 0x200)?;
+        for _ in 0..steps {
+            chip.step()?;
+            chip.v_blank();
+        }
+        Ok(chip)
+    }
+
+    #[test]
+    fn fx1e_overflow_sets_vf() {
+        // I = 0xFFE, V0 = 0x10, ADD I, V0
+        let rom = [0xAF, 0xFE, 0x60, 0x10, 0xF0, 0x1E];
+        let mut quirks = Quirks::new(Mode::SuperChipLegacy);
+        quirks.fx1e_overflow = true;
+        let mut chip = Chip8::new();
+        chip.set_quirks_mode(quirks);
+        chip.load_rom(rom.to_vec(), 0x200).unwrap();
+        for _ in 0..3 {
+            chip.step().unwrap();
+        }
+        assert_eq!(chip.v()[0xF], 1);
+        assert_eq!(chip.i(), 0x00E);
+
+        // without the quirk I just moves past the end, VF is left alone
+        let chip = run("superchiplegacy", &rom, 3).unwrap();
+        assert_eq!(chip.v()[0xF], 0);
+        assert_eq!(chip.i(), 0x100E);
+    }
+
+    #[test]
+    fn fx1e_past_memory_traps_on_use() {
+        // I = 0xFFE, V0 = 0x10, ADD I, V0, LD V0, [I]
+        let rom = [0xAF, 0xFE, 0x60, 0x10, 0xF0, 0x1E, 0xF0, 0x65];
+        assert!(run("superchiplegacy", &rom, 3).is_ok());
+        assert!(run("superchiplegacy", &rom, 4).is_err());
+    }
+
+    #[test]
+    fn dxy0_follows_the_mode() {
+        // I = 0x200 (the program itself), DRW V0, V0, 0
+        let rom = [0xA2, 0x00, 0xD0, 0x00];
+        let lit = |chip: &Chip8| {
+            let screen = chip.get_screen();
+            let screen = screen.lock().unwrap();
+            screen.iter().flatten().filter(|p| p[0]).count()
+        };
+        for mode in ["chip8", "cosmacvip"] {
+            let chip = run(mode, &rom, 2).unwrap();
+            assert_eq!(lit(&chip), 0, "{}", mode);
+        }
+        let chip = run("superchip", &rom, 2).unwrap();
+        assert!(lit(&chip) > 0);
+    }
+
+    #[test]
+    fn megachip_opcodes_need_megachip() {
+        // 0011 (colour display), 02nn (palette) and 01nn nnnn (long I) outside MEGA-CHIP
+        for rom in [
+            [0x00, 0x11, 0x00, 0x00],
+            [0x02, 0x01, 0x00, 0x00],
+            [0x01, 0x12, 0x34, 0x56],
+        ] {
+            let mut quirks = Quirks::new(Mode::XoChip);
+            quirks.platform_check = PlatformCheck::Warn;
+            let mut chip = Chip8::new();
+            chip.set_quirks_mode(quirks);
+            chip.load_rom(rom.to_vec(), 0x200).unwrap();
+            let err = chip.step().unwrap_err();
+            let invalid = matches!(err.error_type, InvalidOpcode(0x202, _));
+            assert!(invalid, "{:?}", err);
+            assert!(chip.mega_screen().is_none());
+            assert_eq!(chip.i(), 0);
+        }
+
+        let chip = run("megachip", &[0x00, 0x11], 1).unwrap();
+        assert!(chip.mega_screen().is_some());
+    }
+
+    #[test]
+    fn pc_wraps_at_the_top_of_memory() {
+        let mut chip = run("xochip", &[], 0).unwrap();
+        // LD V0, 1 at 0xFFFE
+        chip.memory[0xFFFE..].copy_from_slice(&[0x60, 0x01]);
+        chip.pc = 0xFFFE;
+        chip.step().unwrap();
+        assert_eq!((chip.pc, chip.v[0]), (0x0000, 1));
+
+        // SE V1, 0 at 0xFFFC skips past the end
+        chip.memory[0xFFFC..0xFFFE].copy_from_slice(&[0x31, 0x00]);
+        chip.pc = 0xFFFC;
+        chip.step().unwrap();
+        assert_eq!(chip.pc, 0x0000);
+    }
+
+    fn chip8x(rom: &[u8]) -> Chip8 {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("chip8x").unwrap();
+        chip.load_rom(rom.to_vec(), 0x300).unwrap();
+        chip
+    }
+
+    #[test]
+    fn chip8x_add_nibbles() {
+        // V0 = 0x35, V1 = 0x56, 5xy1 adds each nibble modulo 8
+        let mut chip = chip8x(&[0x60, 0x35, 0x61, 0x56, 0x50, 0x11]);
+        for _ in 0..3 {
+            chip.step().unwrap();
+        }
+        assert_eq!(chip.v[0], 0x03);
+        assert_eq!(chip.v[1], 0x56);
+    }
+
+    #[test]
+    fn chip8x_colour_board() {
+        let mut chip = chip8x(&[
+            0x60, 0x10, // V0 = 0x10: columns 0 and 1
+            0x61, 0x00, // V1 = 0x00: block row 0
+            0x62, 0x05, // V2 = 5
+            0xB0, 0x20, // Bxy0
+            0x60, 0x18, // V0 = 24: the column of pixel 24
+            0x61, 0x0A, // V1 = 10: from pixel row 10
+            0xB0, 0x23, // Bxyn, 3 rows
+            0x02, 0xA0, // step the background
+        ]);
+        for _ in 0..7 {
+            chip.step().unwrap();
+        }
+        // Bxyn colours, it doesn't jump
+        assert_eq!(chip.pc, 0x30E);
+        chip.step().unwrap();
+        let board = chip.color_board().unwrap();
+        for y in 0..8 {
+            for x in (0..64).step_by(8) {
+                let filled = y < 4 && x < 16;
+                let expected = if filled { 5 } else { 1 };
+                assert_eq!(board.foreground_at(x, y), expected, "{},{}", x, y);
+            }
+        }
+        for y in 8..14 {
+            let expected = if (10..13).contains(&y) { 5 } else { 1 };
+            assert_eq!(board.foreground_at(24, y), expected, "24,{}", y);
+            assert_eq!(board.foreground_at(31, y), expected, "31,{}", y);
+            assert_eq!(board.foreground_at(32, y), 1, "32,{}", y);
+        }
+
+        // 02A0 cycles through the background colours
+        assert_eq!(board.background, 1);
+        for background in [2, 3, 0] {
+            chip.pc = 0x30E;
+            chip.step().unwrap();
+            assert_eq!(chip.color_board().unwrap().background, background);
+        }
+        assert!(run("chip8", &[], 0).unwrap().color_board().is_none());
+    }
+
+    #[test]
+    fn chip8x_second_keypad() {
+        // V0 = 7, ExF2 skips if it's down on the second keypad, ExF5 if it's up
+        let rom = [0x60, 0x07, 0xE0, 0xF2, 0x00, 0x00, 0xE0, 0xF5];
+        for pressed in [false, true] {
+            let mut chip = chip8x(&rom);
+            chip.set_key2_state(types::Key::Key7, pressed);
+            // the first keypad doesn't count
+            chip.set_key_state(types::Key::Key7, !pressed);
+            chip.step().unwrap();
+            chip.step().unwrap();
+            assert_eq!(chip.pc, if pressed { 0x306 } else { 0x304 });
+            chip.pc = 0x306;
+            chip.step().unwrap();
+            assert_eq!(chip.pc, if pressed { 0x308 } else { 0x30A });
+        }
+    }
+
+    #[test]
+    fn chip8x_ports() {
+        // V0 = 0x40, FxF8 sets the tone, FxFB waits for the input port
+        let mut chip = chip8x(&[0x60, 0x40, 0xF0, 0xF8, 0xF1, 0xFB]);
+        chip.get_sound();
+        chip.step().unwrap();
+        chip.step().unwrap();
+        let sound = chip.get_sound().unwrap();
+        assert_eq!(sound.pitch, chip8x::tone_pitch(0x40));
+        assert_eq!(sound.pattern, chip8x::TONE_PATTERN);
+        // higher values divide the VP-595 clock further
+        assert!(chip8x::tone_pitch(0x40) < chip8x::tone_pitch(0x10));
+
+        for _ in 0..3 {
+            chip.step().unwrap();
+            assert_eq!(chip.pc, 0x304);
+        }
+        chip.set_port_input(0x9C);
+        chip.step().unwrap();
+        assert_eq!((chip.pc, chip.v[1]), (0x306, 0x9C));
+    }
+
+    // Rows and columns of the 128x64 screen lit in layer 0
+    fn lit_pixels(chip: &Chip8) -> Vec<(usize, usize)> {
+        let screen = chip.get_screen();
+        let screen = screen.lock().unwrap();
+        let mut lit = vec![];
+        for (y, row) in screen.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                if pixel[0] {
+                    lit.push((y, x));
+                }
+            }
+        }
+        lit
+    }
+
+    // A 4 pixel wide sprite drawn at 2, 40, then (for HIRES CHIP-8) cleared and drawn twice
+    fn tall_rom(start: usize) -> Vec<u8> {
+        let mut rom = vec![0; 0x100];
+        rom[start - 0x200..start - 0x200 + 14].copy_from_slice(&[
+            0xA2, 0xF0, // I = 0x2F0
+            0x60, 0x02, // V0 = 2
+            0x61, 0x28, // V1 = 40
+            0xD0, 0x11, // DRW V0, V1, 1
+            0x02, 0x30, // CLS 64x64
+            0xD0, 0x11, // DRW V0, V1, 1
+            0xD0, 0x11, // DRW V0, V1, 1 again to collide
+        ]);
+        rom[0xF0] = 0xF0;
+        rom
+    }
+
+    #[test]
+    fn hires_chip8_switches_to_64x64() {
+        let mut rom = tall_rom(HIRES_ENTRY as usize);
+        rom[..2].copy_from_slice(&HIRES_SWITCH.to_be_bytes());
+        let mut chip = run("hireschip8", &rom, 0).unwrap();
+        assert_eq!(chip.resolution(), (64, 32));
+        chip.step().unwrap();
+        assert_eq!((chip.pc, chip.resolution()), (HIRES_ENTRY, (64, 64)));
+
+        let step = |chip: &mut Chip8| {
+            chip.step().unwrap();
+            chip.v_blank();
+            lit_pixels(chip)
+        };
+        // each pixel is 2x1 on the 128x64 screen
+        for _ in 0..3 {
+            step(&mut chip);
+        }
+        let row: Vec<_> = (4..12).map(|x| (40, x)).collect();
+        assert_eq!(step(&mut chip), row);
+        assert!(step(&mut chip).is_empty());
+        assert_eq!(step(&mut chip), row);
+        assert_eq!(chip.v[0xF], 0);
+        assert!(step(&mut chip).is_empty());
+        assert_eq!(chip.v[0xF], 1);
+
+        // 1260 anywhere else is a plain jump
+        let mut chip = run("hireschip8", &[0x00, 0xE0, 0x12, 0x60], 2).unwrap();
+        assert_eq!((chip.pc, chip.resolution()), (0x260, (64, 32)));
+        chip.pc = 0x200;
+        chip.memory[0x200..0x202].copy_from_slice(&[0x02, 0x30]);
+        assert!(chip.step().is_ok());
+        let chip = run("cosmacvip", &rom, 1).unwrap();
+        assert_eq!((chip.pc, chip.resolution()), (0x260, (64, 32)));
+    }
+
+    #[test]
+    fn two_page_draws_64x64() {
+        let chip = run("twopage", &tall_rom(0x200), 4).unwrap();
+        assert_eq!(chip.resolution(), (64, 64));
+        let row: Vec<_> = (4..12).map(|x| (40, x)).collect();
+        assert_eq!(lit_pixels(&chip), row);
+
+        // the same sprite wraps to row 8 of the 64x32 display
+        let chip = run("cosmacvip", &tall_rom(0x200), 4).unwrap();
+        let wrapped: Vec<_> = (4..12).flat_map(|x| [(16, x), (17, x)]).collect();
+        let mut lit = lit_pixels(&chip);
+        lit.sort_by_key(|&(y, x)| (x, y));
+        assert_eq!(lit, wrapped);
+    }
+
+    #[test]
+    fn schip10_draws_8x16_in_lores() {
+        // I = 0x220 (32 bytes of 0xFF), DRW V0, V0, 0
+        let mut rom = vec![0; 0x40];
+        rom[..4].copy_from_slice(&[0xA2, 0x20, 0xD0, 0x00]);
+        rom[0x20..].fill(0xFF);
+        // lores pixels are 2x2, so 8x16 covers 16x32 of the screen
+        for (mode, cols) in [("superchip10", 16), ("superchiplegacy", 32)] {
+            let chip = run(mode, &rom, 2).unwrap();
+            let lit = lit_pixels(&chip);
+            let block: Vec<_> = (0..32)
+                .flat_map(|y| (0..cols).map(move |x| (y, x)))
+                .collect();
+            assert_eq!(lit, block, "{}", mode);
+        }
+
+        // and 16x16 in hires on both
+        rom[..6].copy_from_slice(&[0x00, 0xFF, 0xA2, 0x20, 0xD0, 0x00]);
+        for mode in ["superchip10", "superchiplegacy"] {
+            let chip = run(mode, &rom, 3).unwrap();
+            let block: Vec<_> = (0..16).flat_map(|y| (0..16).map(move |x| (y, x))).collect();
+            assert_eq!(lit_pixels(&chip), block, "{}", mode);
+        }
+    }
+
+    #[test]
+    fn hp48_counts_rows_in_vf() {
+        let rom = [
+            0x00, 0xFF, // HIGH
+            0xA2, 0x20, // I = 0x220 (8 bytes of 0xFF)
+            0x60, 0x3C, // V0 = 60
+            0xD0, 0x08, // DRW V0, V0, 8: 4 rows fall off the bottom
+            0x61, 0x00, // V1 = 0
+            0xD1, 0x18, // DRW V1, V1, 8
+            0xD1, 0x18, // DRW V1, V1, 8: 8 rows collide
+        ];
+        let mut rom = rom.to_vec();
+        rom.resize(0x28, 0xFF);
+        let vf = |mode, steps| run(mode, &rom, steps).unwrap().v[0xF];
+        for mode in ["superchip10", "superchiplegacy"] {
+            assert_eq!(vf(mode, 4), 4, "{}", mode);
+            assert_eq!(vf(mode, 6), 0, "{}", mode);
+            assert_eq!(vf(mode, 7), 8, "{}", mode);
+        }
+        // the modern SuperChip just sets VF on a collision
+        assert_eq!(vf("superchip", 4), 0);
+        assert_eq!(vf("superchip", 7), 1);
+
+        // and in lores the HP-48 does too
+        rom[1] = 0xFE;
+        let chip = run("superchiplegacy", &rom, 7).unwrap();
+        assert_eq!(chip.v[0xF], 1);
+    }
+
+    #[test]
+    fn hp48_lores_quirks() {
+        // DRW V0, V0, 1 from I = 0x206 (0xFF), then SCR
+        let rom = [0xA2, 0x06, 0xD0, 0x01, 0x00, 0xFB, 0xFF];
+        // DRW waits for the display in lores
+        let mut chip = run("superchip10", &rom, 1).unwrap();
+        chip.step().unwrap();
+        assert!(chip.is_blocked());
+        let chip = run("superchiplegacy", &rom, 3).unwrap();
+        // scrolling moves by half a lores pixel
+        let shifted: Vec<_> = (0..2).flat_map(|y| (4..20).map(move |x| (y, x))).collect();
+        assert_eq!(lit_pixels(&chip), shifted);
+        let chip = run("superchip", &rom, 3).unwrap();
+        let shifted: Vec<_> = (0..2).flat_map(|y| (8..24).map(move |x| (y, x))).collect();
+        assert_eq!(lit_pixels(&chip), shifted);
+
+        // no display wait in hires
+        let mut chip = run("superchip10", &[0x00, 0xFF, 0xA2, 0x08, 0xD0, 0x01], 2).unwrap();
+        chip.step().unwrap();
+        assert!(!chip.is_blocked());
+
+        // and no big A-F glyphs
+        let big_a = font::big_glyph(0xA);
+        for mode in ["superchip10", "superchiplegacy"] {
+            let chip = run(mode, &[], 0).unwrap();
+            let blank = chip.memory[big_a..big_a + 10].iter().all(|&b| b == 0);
+            assert!(blank, "{}", mode);
+        }
+        let chip = run("superchip", &[], 0).unwrap();
+        assert!(chip.memory[big_a..big_a + 10].iter().any(|&b| b != 0));
+    }
+}
@@ -0,0 +1,235 @@
+/*
+   MEGA-CHIP: SuperChip with a 256x192 colour display and sampled sound.
+
+   0011 switches to the MEGA display (0010 back to the SuperChip one). There, Dxyn
+   draws sprite_width x sprite_height bytes from I, each a palette index with 0 being
+   transparent, blended into the frame with the blend mode. Font sprites (I below
+   0x200) stay 1 bit and are drawn white. Drawing goes to a back buffer that 00E0 shows
+   and then clears, so frames don't flicker. VF is set when a sprite covers a pixel of
+   the collision colour.
+
+   The palette is loaded by 02nn as nn ARGB colours from I into entries 1..=nn.
+
+   060n plays 8 bit unsigned samples from I, after a header of the sample rate (u16),
+   the length (u24) and a zero byte. n = 0 loops. 0700 stops it.
+*/
+
+pub const MEGA_COLS: usize = 256;
+pub const MEGA_ROWS: usize = 192;
+pub const PALETTE_SIZE: usize = 256;
+pub const SAMPLE_HEADER: usize = 6;
+
+// Sprites below this are the built in font
+pub const FONT_END: usize = 0x200;
+const FONT_COLOR: u32 = 0xFFFFFF;
+// Palette index font pixels leave behind, for collisions
+const FONT_INDEX: u8 = 0xFF;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Blend {
+    Normal,
+    // sprite drawn at 25, 50 or 75% opacity
+    Percent25,
+    Percent50,
+    Percent75,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    pub fn id(&self) -> u8 {
+        match self {
+            Blend::Normal => 0,
+            Blend::Percent25 => 1,
+            Blend::Percent50 => 2,
+            Blend::Percent75 => 3,
+            Blend::Add => 4,
+            Blend::Multiply => 5,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Blend> {
+        match id {
+            0 => Some(Blend::Normal),
+            1 => Some(Blend::Percent25),
+            2 => Some(Blend::Percent50),
+            3 => Some(Blend::Percent75),
+            4 => Some(Blend::Add),
+            5 => Some(Blend::Multiply),
+            _ => None,
+        }
+    }
+
+    // Sprite colour `src` over frame colour `dst`, both 0xRRGGBB
+    pub fn apply(&self, src: u32, dst: u32) -> u32 {
+        let mut out = 0;
+        for shift in [0, 8, 16] {
+            let s = (src >> shift) & 0xFF;
+            let d = (dst >> shift) & 0xFF;
+            let c = match self {
+                Blend::Normal => s,
+                Blend::Percent25 => (s + 3 * d) / 4,
+                Blend::Percent50 => (s + d) / 2,
+                Blend::Percent75 => (3 * s + d) / 4,
+                Blend::Add => (s + d).min(0xFF),
+                Blend::Multiply => s * d / 0xFF,
+            };
+            out |= c << shift;
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Sample {
+    // samples per second
+    pub rate: u32,
+    pub data: Vec<u8>,
+    pub looping: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum SampleEvent {
+    Play(Sample),
+    Stop,
+}
+
+#[derive(Clone, Debug)]
+pub struct MegaScreen {
+    pub enabled: bool,
+    // ARGB, entry 0 is transparent
+    pub palette: Vec<u32>,
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    // opacity of the whole display
+    pub alpha: u8,
+    pub blend: Blend,
+    pub collision_color: u8,
+    // palette index last drawn at each pixel of the back buffer
+    pub(crate) indices: Vec<u8>,
+    // 0xRRGGBB, row by row
+    pub(crate) back: Vec<u32>,
+    pub(crate) front: Vec<u32>,
+}
+
+impl Default for MegaScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MegaScreen {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            palette: vec![0; PALETTE_SIZE],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend: Blend::Normal,
+            collision_color: 0,
+            indices: vec![0; MEGA_COLS * MEGA_ROWS],
+            back: vec![0; MEGA_COLS * MEGA_ROWS],
+            front: vec![0; MEGA_COLS * MEGA_ROWS],
+        }
+    }
+
+    // The frame 00E0 last showed, 0xRRGGBB row by row
+    pub fn frame(&self) -> &[u32] {
+        &self.front
+    }
+
+    // 00E0: show the back buffer and start a new one
+    pub fn present(&mut self) {
+        self.front.copy_from_slice(&self.back);
+        self.back.fill(0);
+        self.indices.fill(0);
+    }
+
+    // 02nn: ARGB colours into entries 1..=count
+    pub fn load_palette(&mut self, colors: &[u8]) {
+        for (i, argb) in colors.chunks_exact(4).enumerate().take(PALETTE_SIZE - 1) {
+            self.palette[i + 1] = u32::from_be_bytes([argb[0], argb[1], argb[2], argb[3]]);
+        }
+    }
+
+    // 03nn/04nn take 0 as 256
+    pub fn sprite_size(&self) -> (usize, usize) {
+        let size = |n: usize| if n == 0 { 256 } else { n };
+        (size(self.sprite_width), size(self.sprite_height))
+    }
+
+    // Dxyn with a sprite of palette indices, true on a collision
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let (width, _) = self.sprite_size();
+        let mut collided = false;
+        for (i, &index) in sprite.iter().enumerate() {
+            if index != 0 {
+                let color = self.palette[index as usize] & 0xFFFFFF;
+                collided |= self.plot(x + i % width, y + i / width, index, color);
+            }
+        }
+        collided
+    }
+
+    // Dxyn with a 1 bit font sprite, 8 pixels wide
+    pub fn draw_font(&mut self, x: usize, y: usize, rows: &[u8]) -> bool {
+        let mut collided = false;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..8 {
+                if bits & (0x80 >> col) != 0 {
+                    collided |= self.plot(x + col, y + row, FONT_INDEX, FONT_COLOR);
+                }
+            }
+        }
+        collided
+    }
+
+    // Sprites are clipped at the edges
+    fn plot(&mut self, x: usize, y: usize, index: u8, color: u32) -> bool {
+        if x >= MEGA_COLS || y >= MEGA_ROWS {
+            return false;
+        }
+        let pos = y * MEGA_COLS + x;
+        let collided = self.indices[pos] != 0 && self.indices[pos] == self.collision_color;
+        self.indices[pos] = index;
+        self.back[pos] = self.blend.apply(color, self.back[pos]);
+        collided
+    }
+
+    // Scrolling moves the back buffer, whatever scrolls in is blank
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let indices = self.indices.clone();
+        let back = self.back.clone();
+        for y in 0..MEGA_ROWS {
+            for x in 0..MEGA_COLS {
+                let src_x = x as isize - dx;
+                let src_y = y as isize - dy;
+                let pos = y * MEGA_COLS + x;
+                if (0..MEGA_COLS as isize).contains(&src_x)
+                    && (0..MEGA_ROWS as isize).contains(&src_y)
+                {
+                    let src = src_y as usize * MEGA_COLS + src_x as usize;
+                    self.indices[pos] = indices[src];
+                    self.back[pos] = back[src];
+                } else {
+                    self.indices[pos] = 0;
+                    self.back[pos] = 0;
+                }
+            }
+        }
+    }
+}
+
+// 060n: the sample starting at `data` (I), None if the header runs past its end
+pub fn read_sample(data: &[u8], looping: bool) -> Option<Sample> {
+    let header = data.get(..SAMPLE_HEADER)?;
+    let rate = u16::from_be_bytes([header[0], header[1]]) as u32;
+    let len = u32::from_be_bytes([0, header[2], header[3], header[4]]) as usize;
+    let end = (SAMPLE_HEADER + len).min(data.len());
+    Some(Sample {
+        rate,
+        data: data[SAMPLE_HEADER..end].to_vec(),
+        looping,
+    })
+}
@@ -0,0 +1,659 @@
+use crate::font::Font;
+use crate::timing::Timing;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Mode {
+    Chip8Modern,
+    SuperChipModern,
+    SuperChipLegacy,
+    XoChip,
+    CosmacVip,
+    Chip8X,
+    MegaChip,
+    HiresChip8,
+    TwoPage,
+    SuperChip10,
+}
+#[derive(Clone)]
+pub struct Quirks {
+    pub mode: Mode,
+    pub mode_label: String,
+    pub vf_reset: bool,
+    pub load_store_index_increase: bool,
+    pub display_wait: bool,
+    pub clipping: bool,
+    pub shifting_vx: bool,
+    pub jump_plus_vx: bool,
+    // Fx1E sets VF when I goes past 0xFFF and wraps I (Amiga interpreter, needed by Spacefight 2091!)
+    pub fx1e_overflow: bool,
+    // 00FE/00FF clear the display when switching resolution
+    pub resolution_clear: bool,
+    // Scrolling in lores moves by half a lores pixel per unit, as on SCHIP 1.1
+    pub lores_scroll_half: bool,
+    // Addressable memory in bytes
+    pub memory_size: usize,
+    // Fx0A only takes a key pressed after it started, beeps while it is held and
+    // returns on release, like the VIP interpreter
+    pub key_release_tone: bool,
+    pub timing: Timing,
+    // Nested calls the platform allows
+    pub stack_depth: usize,
+    // What happens when a ROM uses an opcode, memory or stack depth its platform lacks
+    pub platform_check: PlatformCheck,
+    // Glyphs loaded for Fx29/Fx30
+    pub font: Font,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PlatformCheck {
+    // Stop with an error
+    Trap,
+    // Record a warning and carry on with the full 64 KiB and 16 level stack
+    Warn,
+}
+
+// Deepest stack any platform has
+pub const MAX_STACK_DEPTH: usize = 16;
+// Most memory any platform has (MEGA-CHIP)
+pub const MAX_MEMORY_SIZE: usize = 1 << 24;
+
+impl PlatformCheck {
+    pub fn id(&self) -> u8 {
+        match self {
+            PlatformCheck::Trap => 0,
+            PlatformCheck::Warn => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<PlatformCheck> {
+        match id {
+            0 => Some(PlatformCheck::Trap),
+            1 => Some(PlatformCheck::Warn),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlatformCheck::Trap => "trap",
+            PlatformCheck::Warn => "warn",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PlatformCheck> {
+        match name.to_lowercase().as_str() {
+            "trap" => Some(PlatformCheck::Trap),
+            "warn" => Some(PlatformCheck::Warn),
+            _ => None,
+        }
+    }
+}
+
+impl Mode {
+    // Stable numeric id, matches the <CHIP Mode> CLI argument
+    pub fn id(&self) -> u8 {
+        match self {
+            Mode::Chip8Modern => 1,
+            Mode::SuperChipModern => 2,
+            Mode::SuperChipLegacy => 3,
+            Mode::XoChip => 4,
+            Mode::CosmacVip => 5,
+            Mode::Chip8X => 6,
+            Mode::MegaChip => 7,
+            Mode::HiresChip8 => 8,
+            Mode::TwoPage => 9,
+            Mode::SuperChip10 => 10,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Chip8Modern => "chip8",
+            Mode::SuperChipModern => "superchipmodern",
+            Mode::SuperChipLegacy => "superchiplegacy",
+            Mode::XoChip => "xochip",
+            Mode::CosmacVip => "cosmacvip",
+            Mode::Chip8X => "chip8x",
+            Mode::MegaChip => "megachip",
+            Mode::HiresChip8 => "hireschip8",
+            Mode::TwoPage => "twopage",
+            Mode::SuperChip10 => "superchip10",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name.to_lowercase().as_str() {
+            "chip8modern" | "chip8" => Some(Mode::Chip8Modern),
+            "superchipmodern" | "superchip" => Some(Mode::SuperChipModern),
+            "superchiplegacy" | "superchip11" | "schip11" => Some(Mode::SuperChipLegacy),
+            "superchip10" | "schip10" => Some(Mode::SuperChip10),
+            "xo-chip" | "xochip" => Some(Mode::XoChip),
+            "cosmacvip" | "vip" => Some(Mode::CosmacVip),
+            "chip-8x" | "chip8x" => Some(Mode::Chip8X),
+            "megachip" | "megachip8" | "mega-chip" => Some(Mode::MegaChip),
+            "hireschip8" | "hires-chip-8" | "hires" => Some(Mode::HiresChip8),
+            "twopage" | "two-page" => Some(Mode::TwoPage),
+            _ => None,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Mode> {
+        match id {
+            1 => Some(Mode::Chip8Modern),
+            2 => Some(Mode::SuperChipModern),
+            3 => Some(Mode::SuperChipLegacy),
+            4 => Some(Mode::XoChip),
+            5 => Some(Mode::CosmacVip),
+            6 => Some(Mode::Chip8X),
+            7 => Some(Mode::MegaChip),
+            8 => Some(Mode::HiresChip8),
+            9 => Some(Mode::TwoPage),
+            10 => Some(Mode::SuperChip10),
+            _ => None,
+        }
+    }
+
+    /*
+       SuperChip 1.0 and 1.1 as the HP-48 runs them, see
+       https://github.com/Timendus/chip8-test-suite/blob/main/legacy-superchip.md
+       Hires Dxyn puts the number of colliding or clipped rows in VF and doesn't wait for
+       the display
+    */
+    pub fn is_hp48(&self) -> bool {
+        matches!(self, Mode::SuperChip10 | Mode::SuperChipLegacy)
+    }
+
+    // Where ROMs are loaded and execution begins
+    pub fn program_start(&self) -> u16 {
+        match self {
+            // the CHIP-8X interpreter is bigger and takes 0x200-0x2FF as well
+            Mode::Chip8X => 0x300,
+            _ => 0x200,
+        }
+    }
+}
+
+impl Quirks {
+    pub fn new(mode: Mode) -> Self {
+        match mode {
+            Mode::Chip8Modern => Quirks {
+                mode,
+                mode_label: "Chip8-Modern".to_string(),
+                vf_reset: true,
+                load_store_index_increase: true,
+                display_wait: true,
+                clipping: true,
+                shifting_vx: false,
+                jump_plus_vx: false,
+                fx1e_overflow: false,
+                resolution_clear: true,
+                lores_scroll_half: false,
+                memory_size: 1 << 12,
+                key_release_tone: false,
+                timing: Timing::Instructions,
+                stack_depth: 16,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Octo,
+            },
+            Mode::SuperChipModern => Quirks {
+                mode,
+                mode_label: "SuperChip-Modern".to_string(),
+                vf_reset: false,
+                load_store_index_increase: false,
+                display_wait: false,
+                clipping: true,
+                shifting_vx: true,
+                jump_plus_vx: true,
+                fx1e_overflow: false,
+                resolution_clear: true,
+                lores_scroll_half: false,
+                memory_size: 1 << 12,
+                key_release_tone: false,
+                timing: Timing::Instructions,
+                stack_depth: 16,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Octo,
+            },
+            // SCHIP 1.0 lacks the scroll opcodes and draws Dxy0 as 8x16 in lores
+            Mode::SuperChipLegacy | Mode::SuperChip10 => Quirks {
+                mode,
+                mode_label: match mode {
+                    Mode::SuperChip10 => "SuperChip-1.0",
+                    _ => "SuperChip-1.1",
+                }
+                .to_string(),
+                vf_reset: false,
+                load_store_index_increase: false,
+                display_wait: true,
+                clipping: true,
+                shifting_vx: true,
+                jump_plus_vx: true,
+                fx1e_overflow: false,
+                resolution_clear: false,
+                lores_scroll_half: true,
+                memory_size: 1 << 12,
+                key_release_tone: false,
+                timing: Timing::Instructions,
+                stack_depth: 16,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Schip,
+            },
+            Mode::XoChip => Quirks {
+                mode,
+                mode_label: "xo-chip".to_string(),
+                vf_reset: false,
+                load_store_index_increase: true,
+                display_wait: false,
+                clipping: false,
+                shifting_vx: false,
+                jump_plus_vx: false,
+                fx1e_overflow: false,
+                resolution_clear: true,
+                lores_scroll_half: false,
+                memory_size: 1 << 16,
+                key_release_tone: false,
+                timing: Timing::Instructions,
+                stack_depth: 16,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Octo,
+            },
+            // The original 1977 interpreter: 4 KiB, 64x32 only, Bnnn jumps to V0 + nnn and
+            // shifts read Vy
+            Mode::CosmacVip => Quirks {
+                mode,
+                mode_label: "COSMAC-VIP".to_string(),
+                vf_reset: true,
+                load_store_index_increase: true,
+                display_wait: true,
+                clipping: true,
+                shifting_vx: false,
+                jump_plus_vx: false,
+                fx1e_overflow: false,
+                resolution_clear: true,
+                lores_scroll_half: false,
+                memory_size: 1 << 12,
+                key_release_tone: true,
+                timing: Timing::CosmacVip,
+                stack_depth: 12,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Vip,
+            },
+            // The VIP interpreter extended for the VP-590 colour board and VP-595 sound
+            // board, so it keeps the VIP quirks and timing
+            Mode::Chip8X => Quirks {
+                mode,
+                mode_label: "CHIP-8X".to_string(),
+                vf_reset: true,
+                load_store_index_increase: true,
+                display_wait: true,
+                clipping: true,
+                shifting_vx: false,
+                jump_plus_vx: false,
+                fx1e_overflow: false,
+                resolution_clear: true,
+                lores_scroll_half: false,
+                memory_size: 1 << 12,
+                key_release_tone: true,
+                timing: Timing::CosmacVip,
+                stack_depth: 12,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Vip,
+            },
+            // VIP interpreters patched for a 64x64 display: HIRES CHIP-8 switches to it with
+            // 1260 (see HIRES_SWITCH), the two-page display has it from the start
+            Mode::HiresChip8 | Mode::TwoPage => Quirks {
+                mode,
+                mode_label: match mode {
+                    Mode::HiresChip8 => "HIRES CHIP-8",
+                    _ => "CHIP-8 TWO-PAGE",
+                }
+                .to_string(),
+                vf_reset: true,
+                load_store_index_increase: true,
+                display_wait: true,
+                clipping: true,
+                shifting_vx: false,
+                jump_plus_vx: false,
+                fx1e_overflow: false,
+                resolution_clear: true,
+                lores_scroll_half: false,
+                memory_size: 1 << 12,
+                key_release_tone: true,
+                timing: Timing::CosmacVip,
+                stack_depth: 12,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Vip,
+            },
+            // SuperChip 1.1 quirks, with 01nn nnnn addressing 16 MiB
+            Mode::MegaChip => Quirks {
+                mode,
+                mode_label: "MEGA-CHIP".to_string(),
+                vf_reset: false,
+                load_store_index_increase: false,
+                display_wait: false,
+                clipping: true,
+                shifting_vx: true,
+                jump_plus_vx: true,
+                fx1e_overflow: false,
+                resolution_clear: false,
+                lores_scroll_half: true,
+                memory_size: MAX_MEMORY_SIZE,
+                key_release_tone: false,
+                timing: Timing::Instructions,
+                stack_depth: 16,
+                platform_check: PlatformCheck::Trap,
+                font: Font::Octo,
+            },
+        }
+    }
+}
+
+/*
+   Quirk profiles
+
+   A profile is a named set of quirks stored as "<name> = <value>" lines, '#' starts a
+   comment. `mode` picks the preset the other lines are applied to and the opcodes that
+   are available, `name` is shown as the mode label:
+
+   name = spacefight
+   mode = superchiplegacy
+   fx1e_overflow = true
+   timing = vip
+
+   Booleans are true/false (or on/off, 1/0), memory_size is a number of bytes,
+   stack_depth a number of calls, platform_check is trap or warn and font one of vip,
+   dream6800, eti660, fishnchips, schip or octo.
+*/
+
+// Every quirk that set() accepts, in the order to_profile() writes them
+pub const QUIRK_NAMES: [&str; 15] = [
+    "vf_reset",
+    "load_store_index_increase",
+    "display_wait",
+    "clipping",
+    "shifting_vx",
+    "jump_plus_vx",
+    "fx1e_overflow",
+    "resolution_clear",
+    "lores_scroll_half",
+    "key_release_tone",
+    "memory_size",
+    "timing",
+    "stack_depth",
+    "platform_check",
+    "font",
+];
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "1" => Ok(true),
+        "false" | "off" | "0" => Ok(false),
+        _ => Err(format!("expected true or false, got '{}'", value)),
+    }
+}
+
+fn parse_stack_depth(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(depth) if (1..=MAX_STACK_DEPTH).contains(&depth) => Ok(depth),
+        _ => Err(format!(
+            "stack depth '{}' is not between 1 and {}",
+            value, MAX_STACK_DEPTH
+        )),
+    }
+}
+
+fn parse_memory_size(value: &str) -> Result<usize, String> {
+    let size = match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse::<usize>(),
+    }
+    .map_err(|e| format!("invalid memory size '{}': {}", value, e))?;
+    if !(0x1000..=MAX_MEMORY_SIZE).contains(&size) {
+        return Err(format!(
+            "memory size {} is not between 4096 and {}",
+            size, MAX_MEMORY_SIZE
+        ));
+    }
+    Ok(size)
+}
+
+impl Quirks {
+    // Set one quirk by name, eg. set("vf_reset", "false")
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "vf_reset" => self.vf_reset = parse_bool(value)?,
+            "load_store_index_increase" => self.load_store_index_increase = parse_bool(value)?,
+            "display_wait" => self.display_wait = parse_bool(value)?,
+            "clipping" => self.clipping = parse_bool(value)?,
+            "shifting_vx" => self.shifting_vx = parse_bool(value)?,
+            "jump_plus_vx" => self.jump_plus_vx = parse_bool(value)?,
+            "fx1e_overflow" => self.fx1e_overflow = parse_bool(value)?,
+            "resolution_clear" => self.resolution_clear = parse_bool(value)?,
+            "lores_scroll_half" => self.lores_scroll_half = parse_bool(value)?,
+            "key_release_tone" => self.key_release_tone = parse_bool(value)?,
+            "memory_size" => self.memory_size = parse_memory_size(value)?,
+            "timing" => {
+                self.timing =
+                    Timing::from_name(value).ok_or(format!("unknown timing '{}'", value))?
+            }
+            "stack_depth" => self.stack_depth = parse_stack_depth(value)?,
+            "platform_check" => {
+                self.platform_check = PlatformCheck::from_name(value)
+                    .ok_or(format!("expected trap or warn, got '{}'", value))?
+            }
+            "font" => {
+                self.font = Font::from_name(value).ok_or(format!("unknown font '{}'", value))?
+            }
+            _ => return Err(format!("unknown quirk '{}'", name)),
+        }
+        Ok(())
+    }
+
+    // Apply a "<name>=<value>" override, as given on the command line
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let (name, value) = spec
+            .split_once('=')
+            .ok_or(format!("expected <quirk>=<value>, got '{}'", spec))?;
+        self.set(name.trim(), value.trim())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "vf_reset" => self.vf_reset.to_string(),
+            "load_store_index_increase" => self.load_store_index_increase.to_string(),
+            "display_wait" => self.display_wait.to_string(),
+            "clipping" => self.clipping.to_string(),
+            "shifting_vx" => self.shifting_vx.to_string(),
+            "jump_plus_vx" => self.jump_plus_vx.to_string(),
+            "fx1e_overflow" => self.fx1e_overflow.to_string(),
+            "resolution_clear" => self.resolution_clear.to_string(),
+            "lores_scroll_half" => self.lores_scroll_half.to_string(),
+            "key_release_tone" => self.key_release_tone.to_string(),
+            "memory_size" => self.memory_size.to_string(),
+            "timing" => self.timing.name().to_string(),
+            "stack_depth" => self.stack_depth.to_string(),
+            "platform_check" => self.platform_check.name().to_string(),
+            "font" => self.font.name().to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // Memory the core allocates: just the platform's with Trap, at least 64 KiB with Warn
+    pub fn memory_allocated(&self) -> usize {
+        match self.platform_check {
+            PlatformCheck::Trap => self.memory_size,
+            PlatformCheck::Warn => self.memory_size.max(1 << 16),
+        }
+    }
+
+    pub fn stack_allocated(&self) -> usize {
+        match self.platform_check {
+            PlatformCheck::Trap => self.stack_depth,
+            PlatformCheck::Warn => MAX_STACK_DEPTH,
+        }
+    }
+
+    pub fn parse_profile(text: &str) -> Result<Quirks, String> {
+        let mut lines = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected '<quirk> = <value>'", index + 1))?;
+            lines.push((index + 1, name.trim(), value.trim()));
+        }
+
+        // The preset goes first wherever the mode line is
+        let mode = match lines.iter().find(|(_, name, _)| *name == "mode") {
+            Some((line, _, value)) => Mode::from_name(value)
+                .ok_or(format!("line {}: unknown mode '{}'", line, value))?,
+            None => Mode::Chip8Modern,
+        };
+        let mut quirks = Quirks::new(mode);
+        for (line, name, value) in lines {
+            match name {
+                "mode" => {}
+                "name" => quirks.mode_label = value.to_string(),
+                _ => quirks
+                    .set(name, value)
+                    .map_err(|e| format!("line {}: {}", line, e))?,
+            }
+        }
+        Ok(quirks)
+    }
+
+    pub fn to_profile(&self) -> String {
+        let mut text = format!("name = {}\nmode = {}\n", self.mode_label, self.mode.name());
+        for name in QUIRK_NAMES {
+            if let Some(value) = self.get(name) {
+                text += &format!("{} = {}\n", name, value);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [Mode; 10] = [
+        Mode::Chip8Modern,
+        Mode::SuperChipModern,
+        Mode::SuperChipLegacy,
+        Mode::XoChip,
+        Mode::CosmacVip,
+        Mode::Chip8X,
+        Mode::MegaChip,
+        Mode::HiresChip8,
+        Mode::TwoPage,
+        Mode::SuperChip10,
+    ];
+
+    #[test]
+    fn profile_round_trip() {
+        for mode in MODES {
+            assert_eq!(Mode::from_id(mode.id()), Some(mode));
+            assert_eq!(Mode::from_name(mode.name()), Some(mode));
+
+            let mut quirks = Quirks::new(mode);
+            quirks.vf_reset = !quirks.vf_reset;
+            quirks.fx1e_overflow = !quirks.fx1e_overflow;
+            quirks.stack_depth = 3;
+            quirks.memory_size = 0x2000;
+            quirks.platform_check = PlatformCheck::Warn;
+            let profile = quirks.to_profile();
+            let parsed = Quirks::parse_profile(&profile).unwrap();
+            assert_eq!(parsed.mode, mode);
+            assert_eq!(parsed.mode_label, quirks.mode_label);
+            assert_eq!(parsed.to_profile(), profile);
+            for name in QUIRK_NAMES {
+                assert_eq!(parsed.get(name), quirks.get(name), "{} in {:?}", name, mode);
+            }
+        }
+    }
+
+    #[test]
+    fn profile_format() {
+        let profile = "
+            # comments and blank lines are skipped
+            name = spacefight
+            fx1e_overflow = on   # trailing comment
+            timing = vip
+            mode = superchiplegacy
+            memory_size = 0x2000
+        ";
+        let quirks = Quirks::parse_profile(profile).unwrap();
+        // the mode preset is applied first wherever its line is
+        assert_eq!(quirks.mode, Mode::SuperChipLegacy);
+        assert!(quirks.jump_plus_vx);
+        assert_eq!(quirks.mode_label, "spacefight");
+        assert!(quirks.fx1e_overflow);
+        assert_eq!(quirks.timing, Timing::CosmacVip);
+        assert_eq!(quirks.memory_size, 0x2000);
+
+        let quirks = Quirks::parse_profile("").unwrap();
+        assert_eq!(quirks.mode, Mode::Chip8Modern);
+    }
+
+    #[test]
+    fn profile_errors() {
+        let errors = [
+            (
+                "clipping = true\nwobble = 1",
+                "line 2: unknown quirk 'wobble'",
+            ),
+            ("mode = vip\nmode", "line 2: expected '<quirk> = <value>'"),
+            ("mode = pdp11", "line 1: unknown mode 'pdp11'"),
+            (
+                "clipping = yes",
+                "line 1: expected true or false, got 'yes'",
+            ),
+            (
+                "stack_depth = 0",
+                "line 1: stack depth '0' is not between 1 and 16",
+            ),
+            (
+                "stack_depth = 17",
+                "line 1: stack depth '17' is not between 1 and 16",
+            ),
+            (
+                "memory_size = 4095",
+                "line 1: memory size 4095 is not between 4096 and 16777216",
+            ),
+            (
+                "platform_check = ignore",
+                "line 1: expected trap or warn, got 'ignore'",
+            ),
+            ("timing = turbo", "line 1: unknown timing 'turbo'"),
+            ("font = comic", "line 1: unknown font 'comic'"),
+        ];
+        for (profile, error) in errors {
+            assert_eq!(Quirks::parse_profile(profile).err().as_deref(), Some(error));
+        }
+        assert!(Quirks::parse_profile("memory_size = 0xZZ").is_err());
+    }
+
+    #[test]
+    fn apply() {
+        let mut quirks = Quirks::new(Mode::Chip8Modern);
+        quirks.apply(" shifting_vx = true ").unwrap();
+        assert!(quirks.shifting_vx);
+        quirks.apply("stack_depth=16").unwrap();
+        assert_eq!(quirks.stack_depth, 16);
+
+        assert_eq!(
+            quirks.apply("shifting_vx").err().as_deref(),
+            Some("expected <quirk>=<value>, got 'shifting_vx'")
+        );
+        assert!(quirks.apply("shifting_vx=maybe").is_err());
+        assert!(quirks.apply("mode=vip").is_err());
+        // a failed override leaves the quirk alone
+        assert!(quirks.shifting_vx);
+        assert_eq!(quirks.get("mode"), None);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+
+/*
+   Rewind history built on save_state() snapshots.
+
+   Only the newest snapshot is kept in full. Every older frame is stored as the XOR of
+   itself and the frame after it, run-length encoded. Consecutive frames differ in very
+   few bytes (registers, a handful of screen pixels), so most deltas are tiny even
+   though every snapshot carries the full 64 KiB of memory.
+
+   Because XOR is its own inverse, stepping back one frame is just
+   `latest ^= decode(delta)` and the oldest frame can be dropped without touching
+   the others.
+
+   Delta encoding: repeated (zero_run: varint, literal_len: varint, literal bytes...)
+*/
+
+pub struct RewindBuffer {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    delta_bytes: usize,
+    max_frames: usize,
+    max_bytes: usize,
+}
+
+impl RewindBuffer {
+    // max_frames: how many frames back we can go, max_bytes: memory budget for the deltas
+    pub fn new(max_frames: usize, max_bytes: usize) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+            max_frames,
+            max_bytes,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+
+    // Number of frames we can currently step back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    // Approximate memory used by the history, including the full newest snapshot
+    pub fn size_bytes(&self) -> usize {
+        self.delta_bytes + self.latest.as_ref().map_or(0, |l| l.len())
+    }
+
+    // Record the state at the end of a frame
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(latest) = &self.latest {
+            if latest.len() != state.len() {
+                // Snapshot layout changed (eg. a different core build), history is useless
+                self.clear();
+            } else {
+                let delta = encode_delta(latest, &state);
+                self.delta_bytes += delta.len();
+                self.deltas.push_back(delta);
+            }
+        }
+        self.latest = Some(state);
+
+        while self.deltas.len() > self.max_frames || self.delta_bytes > self.max_bytes {
+            match self.deltas.pop_front() {
+                Some(d) => self.delta_bytes -= d.len(),
+                None => break,
+            }
+        }
+    }
+
+    // Step back one frame and return that frame's state, or None if the history is exhausted.
+    // The returned frame becomes the newest entry, so push() continues from it.
+    pub fn rewind(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.delta_bytes -= delta.len();
+        let latest = self.latest.as_mut()?;
+        apply_delta(latest, &delta);
+        Some(latest.clone())
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push((v as u8 & 0x7F) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut v = 0usize;
+    let mut shift = 0;
+    while *pos < data.len() {
+        let b = data[*pos];
+        *pos += 1;
+        v |= ((b & 0x7F) as usize) << shift;
+        if b & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    v
+}
+
+fn encode_delta(prev: &[u8], next: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut pos = 0;
+    while pos < next.len() {
+        let zero_start = pos;
+        while pos < next.len() && prev[pos] == next[pos] {
+            pos += 1;
+        }
+        if pos == next.len() {
+            break;
+        }
+        let literal_start = pos;
+        while pos < next.len() && prev[pos] != next[pos] {
+            pos += 1;
+        }
+        write_varint(&mut out, literal_start - zero_start);
+        write_varint(&mut out, pos - literal_start);
+        out.extend((literal_start..pos).map(|i| prev[i] ^ next[i]));
+    }
+    out
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut out_pos = 0;
+    while pos < delta.len() {
+        out_pos += read_varint(delta, &mut pos);
+        let literal_len = read_varint(delta, &mut pos);
+        for _ in 0..literal_len {
+            state[out_pos] ^= delta[pos];
+            out_pos += 1;
+            pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    // Frames with short and long (multi-byte varint) runs of changes
+    fn frames(count: usize) -> Vec<Vec<u8>> {
+        let mut frame = vec![0u8; 1000];
+        let mut out = vec![];
+        for n in 0..count {
+            frame[n] ^= 0xFF;
+            for b in &mut frame[300..300 + n * 40] {
+                *b = b.wrapping_add(n as u8 + 1);
+            }
+            frame[999] = n as u8;
+            out.push(frame.clone());
+        }
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let frames = frames(16);
+        let mut buffer = RewindBuffer::new(100, usize::MAX);
+        for frame in frames.iter() {
+            buffer.push(frame.clone());
+        }
+        assert_eq!(buffer.len(), 15);
+        for frame in frames.iter().rev().skip(1) {
+            assert_eq!(buffer.rewind().as_ref(), Some(frame));
+        }
+        assert_eq!(buffer.rewind(), None);
+
+        // history continues from the rewound frame
+        buffer.push(frames[5].clone());
+        assert_eq!(buffer.rewind().as_ref(), Some(&frames[0]));
+    }
+
+    #[test]
+    fn machine_states() {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("chip8").unwrap();
+        // LD V0, 1; ADD V1, V0; LD F, V1; DRW V1, V1, 5; JP 0x202
+        let rom = vec![0x60, 0x01, 0x81, 0x04, 0xF1, 0x29, 0xD1, 0x15, 0x12, 0x02];
+        chip.load_rom(rom, 0x200).unwrap();
+        let mut buffer = RewindBuffer::new(100, usize::MAX);
+        let mut states = vec![];
+        for _ in 0..20 {
+            for _ in 0..4 {
+                chip.step().unwrap();
+            }
+            chip.v_blank();
+            states.push(chip.save_state());
+            buffer.push(chip.save_state());
+        }
+        for state in states.iter().rev().skip(1) {
+            let rewound = buffer.rewind().unwrap();
+            assert!(rewound == *state);
+            chip.load_state(&rewound).unwrap();
+        }
+        assert_eq!(chip.v()[1], 1);
+    }
+
+    #[test]
+    fn limits() {
+        let frames = frames(10);
+        let mut buffer = RewindBuffer::new(3, usize::MAX);
+        for frame in frames.iter() {
+            buffer.push(frame.clone());
+        }
+        assert_eq!(buffer.len(), 3);
+        for _ in 0..3 {
+            buffer.rewind().unwrap();
+        }
+        assert_eq!(buffer.rewind(), None);
+
+        let mut buffer = RewindBuffer::new(100, 500);
+        for frame in frames.iter() {
+            buffer.push(frame.clone());
+        }
+        assert!(buffer.size_bytes() - frames[9].len() <= 500);
+        assert!(!buffer.is_empty() && buffer.len() < 9);
+
+        // a different snapshot size drops the history
+        buffer.push(vec![0; 10]);
+        assert!(buffer.is_empty());
+    }
+}
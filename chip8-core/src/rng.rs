@@ -0,0 +1,157 @@
+/*
+   Random number source for Cxkk.
+
+   The generator is part of the machine state (it's saved with save_state()) so a run is
+   fully reproducible from its seed and inputs.
+
+   Xorshift    - xorshift32, decent quality, the default
+   CosmacVip   - modelled on the COSMAC VIP interpreter's routine: the low byte of a
+                 16-bit seed is incremented and used to index a byte in page 0x01 of the
+                 interpreter's own code (VIP_PAGE below), which is added to the high byte.
+                 The new high byte is the random number. Our memory doesn't hold the
+                 interpreter, so the page is kept here rather than read from 0x100-0x1FF.
+*/
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RngKind {
+    Xorshift,
+    CosmacVip,
+}
+
+impl RngKind {
+    pub fn id(&self) -> u8 {
+        match self {
+            RngKind::Xorshift => 0,
+            RngKind::CosmacVip => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<RngKind> {
+        match id {
+            0 => Some(RngKind::Xorshift),
+            1 => Some(RngKind::CosmacVip),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RngKind> {
+        match name.to_lowercase().as_str() {
+            "xorshift" => Some(RngKind::Xorshift),
+            "vip" | "cosmacvip" => Some(RngKind::CosmacVip),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_SEED: u32 = 0x2545_F491;
+
+#[derive(Clone)]
+pub struct Rng {
+    pub(crate) kind: RngKind,
+    pub(crate) state: u32,
+}
+
+impl Rng {
+    pub fn new(kind: RngKind, seed: u32) -> Self {
+        let state = match kind {
+            // xorshift gets stuck at 0
+            RngKind::Xorshift if seed == 0 => DEFAULT_SEED,
+            RngKind::CosmacVip => seed & 0xFFFF,
+            _ => seed,
+        };
+        Self { kind, state }
+    }
+
+    pub fn kind(&self) -> RngKind {
+        self.kind
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        match self.kind {
+            RngKind::Xorshift => {
+                let mut x = self.state;
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                self.state = x;
+                (x >> 24) as u8
+            }
+            RngKind::CosmacVip => {
+                let lo = (self.state as u8).wrapping_add(1);
+                let hi = (self.state >> 8) as u8;
+                let hi = hi.wrapping_add(VIP_PAGE[lo as usize]);
+                self.state = (hi as u32) << 8 | lo as u32;
+                hi
+            }
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(RngKind::Xorshift, DEFAULT_SEED)
+    }
+}
+
+// 0x0100-0x01FF of the COSMAC VIP CHIP-8 interpreter
+const VIP_PAGE: [u8; 256] = [
+    0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC, 0x22, 0xDC, 0x12, 0x56, 0xD4,
+    0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A, 0xF4, 0xAA, 0x3B, 0x28, 0x9A,
+    0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA, 0x0A, 0xAA, 0xD4, 0xE6, 0x06,
+    0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A, 0x0E, 0xF5, 0x3B, 0x4B, 0x56,
+    0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F, 0x56, 0x2A, 0x2A, 0xD4, 0x00,
+    0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17, 0x1A, 0x3A, 0x5B, 0x12, 0xD4,
+    0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17, 0x1A, 0x3A, 0x6B, 0x12, 0xD4,
+    0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA, 0x0F, 0xB5, 0xD4, 0x45, 0xE6,
+    0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88, 0xD4, 0x45, 0x07, 0x30, 0x8C,
+    0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88, 0xD4, 0x3E, 0x88, 0xD4, 0xF8,
+    0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2, 0xFC, 0x01, 0xB5, 0xD4, 0x45,
+    0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A, 0xC4, 0x07, 0x56, 0xD4, 0xAF,
+    0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2, 0x56, 0xF8, 0xFF, 0xA6, 0xF8,
+    0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE, 0xF4, 0x56, 0x76, 0xE6, 0xF4,
+    0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F, 0xBA, 0xD4, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x00,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every byte value comes up, and evenly over a full period of the seed
+    fn assert_spread(kind: RngKind, draws: usize, max_count: usize) {
+        let mut rng = Rng::new(kind, 0);
+        let mut counts = [0usize; 256];
+        for _ in 0..draws {
+            counts[rng.next_byte() as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 0), "{:?} misses values", kind);
+        assert!(
+            counts.iter().all(|&c| c <= max_count),
+            "{:?} repeats values",
+            kind
+        );
+    }
+
+    #[test]
+    fn vip_distribution() {
+        assert_spread(RngKind::CosmacVip, 1 << 16, 256);
+        // and it isn't stuck on a few values in the short run either
+        let mut rng = Rng::new(RngKind::CosmacVip, 0x1234);
+        let distinct: std::collections::BTreeSet<u8> = (0..1024).map(|_| rng.next_byte()).collect();
+        assert!(distinct.len() > 200);
+    }
+
+    #[test]
+    fn xorshift_distribution() {
+        assert_spread(RngKind::Xorshift, 1 << 16, 400);
+    }
+
+    #[test]
+    fn seeds_reproduce() {
+        for kind in [RngKind::Xorshift, RngKind::CosmacVip] {
+            let mut a = Rng::new(kind, 42);
+            let mut b = Rng::new(kind, 42);
+            assert!((0..100).all(|_| a.next_byte() == b.next_byte()));
+        }
+    }
+}
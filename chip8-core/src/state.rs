@@ -0,0 +1,539 @@
+use crate::error::CoreErrorType::InvalidState;
+use crate::chip8x::{ColorBoard, BACKGROUND_COLORS, FOREGROUND_COLORS, ZONE_COLS, ZONE_ROWS};
+use crate::error::*;
+use crate::font::Font;
+use crate::megachip::{Blend, MegaScreen, MEGA_COLS, MEGA_ROWS, PALETTE_SIZE};
+use crate::quirks::{Mode, PlatformCheck, Quirks, MAX_STACK_DEPTH};
+use crate::rng::{Rng, RngKind};
+use crate::timing::Timing;
+use crate::{Chip8, DISPLAY_COLS, DISPLAY_LAYERS, DISPLAY_ROWS, PATTERN_SIZE, RPL_FLAGS};
+
+/*
+   Save state layout (all integers big-endian):
+
+   magic      "C8SS"
+   version    u16
+   memory     u32 length + bytes
+   v          u32 length + bytes
+   rpl        u32 length + 16 bytes
+   stack      u32 length + u16 entries
+   pc         u16
+   i          u32
+   sp         u16
+   dt, st     u8
+   flags      hires_mode, tall_mode, halted_for_input, waiting_for_vblank, exited (u8 each)
+   halt_input_register, bit_plane_selector  u8
+   quirks     mode id u8, label (u32 length + UTF-8), the 10 quirk flags in Quirks field
+              order, memory_size u32, timing id u8,
+              stack_depth u8, platform_check id u8, font id u8
+   sound      pitch u8, u32 length + 16 pattern bytes
+   rng        kind id u8, state u32
+   colours    CHIP-8X background u8, u32 length + one foreground byte per zone
+   mega       u8 1 in MEGA-CHIP mode, then enabled u8, sprite width u8, sprite height u8,
+              alpha u8, blend id u8, collision colour u8, the palette, back and front
+              buffers as u32 length + u32 entries and the index buffer as u32 length +
+              bytes. Other modes have a single u8 0
+   screen     rows u16, cols u16, then one byte per pixel holding its bit plane mask
+*/
+
+const STATE_MAGIC: &[u8; 4] = b"C8SS";
+pub const STATE_VERSION: u16 = 10;
+
+pub(crate) struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { buf: vec![] }
+    }
+
+    pub fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn bool(&mut self, v: bool) {
+        self.buf.push(v as u8);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn u32s(&mut self, v: &[u32]) {
+        self.u32(v.len() as u32);
+        for x in v {
+            self.u32(*x);
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CoreError> {
+        if self.pos + len > self.data.len() {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("truncated at byte {}", self.pos)),
+            ));
+        }
+        let out = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, CoreError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, CoreError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, CoreError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, CoreError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], CoreError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    // Like bytes(), but the length must match what this build of the core expects
+    pub fn bytes_exact(&mut self, name: &str, expected: usize) -> Result<&'a [u8], CoreError> {
+        let b = self.bytes()?;
+        if b.len() != expected {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("{} size {} (expected {})", name, b.len(), expected)),
+            ));
+        }
+        Ok(b)
+    }
+
+    // u32 entries written by StateWriter::u32s(), `expected` of them
+    pub fn u32s_exact(&mut self, name: &str, expected: usize) -> Result<Vec<u32>, CoreError> {
+        let len = self.u32()? as usize;
+        if len != expected {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("{} size {} (expected {})", name, len, expected)),
+            ));
+        }
+        let b = self.take(len * 4)?;
+        Ok(b.chunks_exact(4)
+            .map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+            .collect())
+    }
+}
+
+impl Chip8 {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        for b in STATE_MAGIC {
+            w.u8(*b);
+        }
+        w.u16(STATE_VERSION);
+
+        w.bytes(&self.memory);
+        w.bytes(&self.v);
+        w.bytes(&self.rpl);
+        w.u32(self.stack.len() as u32);
+        for s in self.stack.iter() {
+            w.u16(*s);
+        }
+        w.u16(self.pc);
+        w.u32(self.i);
+        w.u16(self.sp);
+        w.u8(self.dt);
+        w.u8(self.st);
+
+        w.bool(self.hires_mode);
+        w.bool(self.tall_mode);
+        w.bool(self.halted_for_input);
+        w.bool(self.waiting_for_vblank);
+        w.bool(self.exited);
+        w.u8(self.halt_input_register);
+        w.u8(self.bit_plane_selector);
+
+        w.u8(self.quirks.mode.id());
+        w.bytes(self.quirks.mode_label.as_bytes());
+        w.bool(self.quirks.vf_reset);
+        w.bool(self.quirks.load_store_index_increase);
+        w.bool(self.quirks.display_wait);
+        w.bool(self.quirks.clipping);
+        w.bool(self.quirks.shifting_vx);
+        w.bool(self.quirks.jump_plus_vx);
+        w.bool(self.quirks.fx1e_overflow);
+        w.bool(self.quirks.resolution_clear);
+        w.bool(self.quirks.lores_scroll_half);
+        w.bool(self.quirks.key_release_tone);
+        w.u32(self.quirks.memory_size as u32);
+        w.u8(self.quirks.timing.id());
+        w.u8(self.quirks.stack_depth as u8);
+        w.u8(self.quirks.platform_check.id());
+        w.u8(self.quirks.font.id());
+
+        w.u8(self.sound.pitch);
+        w.bytes(&self.sound.pattern);
+
+        w.u8(self.rng.kind.id());
+        w.u32(self.rng.state);
+
+        w.u8(self.color_board.background);
+        w.bytes(&self.color_board.zones);
+
+        w.bool(self.quirks.mode == Mode::MegaChip);
+        if self.quirks.mode == Mode::MegaChip {
+            let mega = &self.mega;
+            w.bool(mega.enabled);
+            w.u8(mega.sprite_width as u8);
+            w.u8(mega.sprite_height as u8);
+            w.u8(mega.alpha);
+            w.u8(mega.blend.id());
+            w.u8(mega.collision_color);
+            w.u32s(&mega.palette);
+            w.u32s(&mega.back);
+            w.u32s(&mega.front);
+            w.bytes(&mega.indices);
+        }
+
+        let screen = self.screen.lock().unwrap();
+        w.u16(DISPLAY_ROWS as u16);
+        w.u16(DISPLAY_COLS as u16);
+        for row in screen.iter() {
+            for cell in row.iter() {
+                let mut planes = 0u8;
+                for (layer, on) in cell.iter().enumerate() {
+                    if *on {
+                        planes |= 1 << layer;
+                    }
+                }
+                w.u8(planes);
+            }
+        }
+
+        w.finish()
+    }
+
+    // Restores a snapshot made by save_state(). On error the machine is left untouched
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), CoreError> {
+        let mut r = StateReader::new(data);
+        let magic = r.take(STATE_MAGIC.len())?;
+        if magic != STATE_MAGIC {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState("not a save state".to_string()),
+            ));
+        }
+        let version = r.u16()?;
+        if version != STATE_VERSION {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!(
+                    "unsupported version {} (expected {})",
+                    version, STATE_VERSION
+                )),
+            ));
+        }
+
+        // checked against the mode's memory size below
+        let memory = r.bytes()?.to_vec();
+        let v = r.bytes_exact("registers", self.v.len())?.to_vec();
+        let rpl = r.bytes_exact("rpl flags", RPL_FLAGS)?.to_vec();
+        // checked against the mode's stack depth below
+        let stack_len = r.u32()? as usize;
+        if stack_len > MAX_STACK_DEPTH {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("stack size {}", stack_len)),
+            ));
+        }
+        let mut stack = Vec::with_capacity(stack_len);
+        for _ in 0..stack_len {
+            stack.push(r.u16()?);
+        }
+        let pc = r.u16()?;
+        let i = r.u32()?;
+        let sp = r.u16()?;
+        let dt = r.u8()?;
+        let st = r.u8()?;
+
+        let hires_mode = r.bool()?;
+        let tall_mode = r.bool()?;
+        let halted_for_input = r.bool()?;
+        let waiting_for_vblank = r.bool()?;
+        let exited = r.bool()?;
+        let halt_input_register = r.u8()?;
+        if halt_input_register as usize >= v.len() {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("input register {}", halt_input_register)),
+            ));
+        }
+        let bit_plane_selector = r.u8()?;
+
+        let mode_id = r.u8()?;
+        let mode = Mode::from_id(mode_id).ok_or(CoreError::new(
+            err_info!(),
+            InvalidState(format!("unknown mode id {}", mode_id)),
+        ))?;
+        let mut quirks = Quirks::new(mode);
+        quirks.mode_label = String::from_utf8_lossy(r.bytes()?).to_string();
+        quirks.vf_reset = r.bool()?;
+        quirks.load_store_index_increase = r.bool()?;
+        quirks.display_wait = r.bool()?;
+        quirks.clipping = r.bool()?;
+        quirks.shifting_vx = r.bool()?;
+        quirks.jump_plus_vx = r.bool()?;
+        quirks.fx1e_overflow = r.bool()?;
+        quirks.resolution_clear = r.bool()?;
+        quirks.lores_scroll_half = r.bool()?;
+        quirks.key_release_tone = r.bool()?;
+        quirks.memory_size = r.u32()? as usize;
+        let timing_id = r.u8()?;
+        quirks.timing = Timing::from_id(timing_id).ok_or(CoreError::new(
+            err_info!(),
+            InvalidState(format!("unknown timing id {}", timing_id)),
+        ))?;
+        quirks.stack_depth = r.u8()? as usize;
+        let check_id = r.u8()?;
+        quirks.platform_check = PlatformCheck::from_id(check_id).ok_or(CoreError::new(
+            err_info!(),
+            InvalidState(format!("unknown platform check id {}", check_id)),
+        ))?;
+        let font_id = r.u8()?;
+        quirks.font = Font::from_id(font_id).ok_or(CoreError::new(
+            err_info!(),
+            InvalidState(format!("unknown font id {}", font_id)),
+        ))?;
+        if !(1..=MAX_STACK_DEPTH).contains(&quirks.stack_depth)
+            || stack.len() != quirks.stack_allocated()
+            || sp as usize > stack.len()
+        {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("stack size {}", stack.len())),
+            ));
+        }
+        if memory.len() != quirks.memory_allocated() {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("memory size {}", memory.len())),
+            ));
+        }
+
+        let pitch = r.u8()?;
+        let pattern = r.bytes_exact("audio pattern", PATTERN_SIZE)?.to_vec();
+
+        let rng_id = r.u8()?;
+        let rng_kind = RngKind::from_id(rng_id).ok_or(CoreError::new(
+            err_info!(),
+            InvalidState(format!("unknown rng id {}", rng_id)),
+        ))?;
+        let rng = Rng {
+            kind: rng_kind,
+            state: r.u32()?,
+        };
+        // xorshift never leaves 0
+        if rng.kind == RngKind::Xorshift && rng.state == 0 {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState("xorshift state 0".to_string()),
+            ));
+        }
+
+        let background = r.u8()?;
+        if background as usize >= BACKGROUND_COLORS.len() {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("background colour {}", background)),
+            ));
+        }
+        let zones = r.bytes_exact("colour zones", ZONE_COLS * ZONE_ROWS)?.to_vec();
+        if let Some(color) = zones.iter().find(|&&c| c as usize >= FOREGROUND_COLORS.len()) {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("foreground colour {}", color)),
+            ));
+        }
+
+        let mut mega = MegaScreen::new();
+        if r.bool()? {
+            mega.enabled = r.bool()?;
+            mega.sprite_width = r.u8()? as usize;
+            mega.sprite_height = r.u8()? as usize;
+            mega.alpha = r.u8()?;
+            let blend_id = r.u8()?;
+            mega.blend = Blend::from_id(blend_id).ok_or(CoreError::new(
+                err_info!(),
+                InvalidState(format!("unknown blend mode {}", blend_id)),
+            ))?;
+            mega.collision_color = r.u8()?;
+            mega.palette = r.u32s_exact("palette", PALETTE_SIZE)?;
+            mega.back = r.u32s_exact("back buffer", MEGA_COLS * MEGA_ROWS)?;
+            mega.front = r.u32s_exact("front buffer", MEGA_COLS * MEGA_ROWS)?;
+            mega.indices = r.bytes_exact("index buffer", MEGA_COLS * MEGA_ROWS)?.to_vec();
+        }
+
+        let rows = r.u16()? as usize;
+        let cols = r.u16()? as usize;
+        if rows != DISPLAY_ROWS || cols != DISPLAY_COLS {
+            return Err(CoreError::new(
+                err_info!(),
+                InvalidState(format!("screen size {}x{}", cols, rows)),
+            ));
+        }
+        let pixels = r.take(rows * cols)?;
+
+        self.memory = memory;
+        self.v = v;
+        self.rpl = rpl;
+        self.stack = stack;
+        self.pc = pc;
+        self.i = i;
+        self.sp = sp;
+        self.dt = dt;
+        self.st = st;
+        self.hires_mode = hires_mode;
+        self.tall_mode = tall_mode;
+        self.halted_for_input = halted_for_input;
+        self.input_key = None;
+        self.waiting_for_vblank = waiting_for_vblank;
+        self.exited = exited;
+        self.halt_input_register = halt_input_register;
+        self.bit_plane_selector = bit_plane_selector;
+        self.quirks = quirks;
+        self.sound.pitch = pitch;
+        self.sound.pattern = pattern;
+        self.sound.dirty = true;
+        self.rng = rng;
+        self.color_board = ColorBoard { background, zones };
+        self.mega = mega;
+
+        let mut screen = self.screen.lock().unwrap();
+        for (row_ind, row) in screen.iter_mut().enumerate() {
+            for (col_ind, cell) in row.iter_mut().enumerate() {
+                let planes = pixels[row_ind * cols + col_ind];
+                for (layer, on) in cell.iter_mut().enumerate().take(DISPLAY_LAYERS) {
+                    *on = (planes >> layer) & 0b1 == 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A machine part way through a ROM that draws, calls and sets up registers
+    fn running_chip() -> Chip8 {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("xochip").unwrap();
+        let rom = vec![
+            0x60, 0x05, // LD V0, 5
+            0xF0, 0x29, // LD F, V0
+            0x61, 0x0A, // LD V1, 10
+            0xD1, 0x15, // DRW V1, V1, 5
+            0x22, 0x0C, // CALL 0x20C
+            0x12, 0x0A, // JP 0x20A
+            0x6E, 0x42, // LD VE, 0x42
+            0x12, 0x0A, // JP 0x20A
+        ];
+        chip.load_rom(rom, 0x200).unwrap();
+        for _ in 0..6 {
+            chip.step().unwrap();
+        }
+        chip
+    }
+
+    fn assert_rejected(chip: &Chip8) {
+        let state = chip.save_state();
+        let mut target = running_chip();
+        let before = target.save_state();
+        let err = target.load_state(&state).unwrap_err();
+        assert!(matches!(err.error_type, InvalidState(_)), "{}", err);
+        assert_eq!(target.save_state(), before, "machine changed on a failed load");
+    }
+
+    #[test]
+    fn round_trip() {
+        let chip = running_chip();
+        let state = chip.save_state();
+        let mut restored = Chip8::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.save_state(), state);
+        assert_eq!(restored.pc(), chip.pc());
+        assert_eq!(restored.v(), chip.v());
+        assert_eq!(restored.stack(), chip.stack());
+        assert_eq!(
+            *restored.get_screen().lock().unwrap(),
+            *chip.get_screen().lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn truncated_and_foreign_data() {
+        let state = running_chip().save_state();
+        let mut chip = Chip8::new();
+        for len in [0, 3, 6, state.len() / 2, state.len() - 1] {
+            assert!(chip.load_state(&state[..len]).is_err(), "length {}", len);
+        }
+        let mut bad_magic = state.clone();
+        bad_magic[0] = b'X';
+        assert!(chip.load_state(&bad_magic).is_err());
+        let mut bad_version = state;
+        bad_version[5] ^= 0xFF;
+        assert!(chip.load_state(&bad_version).is_err());
+    }
+
+    #[test]
+    fn input_register_out_of_range() {
+        let mut chip = running_chip();
+        chip.halt_input_register = 16;
+        assert_rejected(&chip);
+    }
+
+    #[test]
+    fn short_audio_pattern() {
+        let mut chip = running_chip();
+        chip.sound.pattern = vec![0; 3];
+        assert_rejected(&chip);
+    }
+
+    #[test]
+    fn stuck_xorshift() {
+        let mut chip = running_chip();
+        chip.rng.state = 0;
+        assert_rejected(&chip);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/*
+   Symbol map (name -> address) for the debugger, disassembly and traces.
+
+   A symbol file holds one symbol per line in either of these forms:
+
+   draw_player = 0x2A4
+   :const draw_player 0x2A4
+
+   Numbers are decimal or 0x hex, '#' and ';' start comments. Addresses are shown as
+   the nearest symbol at or below them plus an offset, eg. draw_player+0x6. Octo uses
+   :const for plain numbers (sprite heights, counts) as well, so those only name the
+   exact address and are never the base of an offset.
+*/
+
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    by_name: BTreeMap<String, u16>,
+    // several names can share an address, the first one loaded is shown
+    by_addr: BTreeMap<u16, String>,
+    // :const values, only matched exactly
+    constants: BTreeMap<u16, String>,
+}
+
+#[derive(Debug)]
+pub struct SymbolError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse::<u16>().ok(),
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| SymbolError {
+                line: index + 1,
+                message,
+            };
+            let line = line.split(['#', ';']).next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value, constant) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim(), false),
+                None => match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                    [":const", name, value] => (name, value, true),
+                    _ => return Err(error(format!("expected 'name = address': {}", line))),
+                },
+            };
+            if !valid_name(name) {
+                return Err(error(format!("invalid symbol name '{}'", name)));
+            }
+            let Some(addr) = parse_number(value) else {
+                return Err(error(format!("invalid address '{}'", value)));
+            };
+            if constant {
+                table.insert_constant(name, addr);
+            } else {
+                table.insert(name, addr);
+            }
+        }
+        Ok(table)
+    }
+
+    // Labels of an assembled program, see asm::Program
+    pub fn from_labels(labels: &BTreeMap<String, u16>) -> Self {
+        let mut table = Self::new();
+        for (name, &addr) in labels.iter() {
+            table.insert(name, addr);
+        }
+        table
+    }
+
+    pub fn insert(&mut self, name: &str, addr: u16) {
+        self.forget(name);
+        self.by_name.insert(name.to_string(), addr);
+        self.by_addr.entry(addr).or_insert_with(|| name.to_string());
+    }
+
+    // A name that may not be an address, see the note at the top
+    pub fn insert_constant(&mut self, name: &str, value: u16) {
+        self.forget(name);
+        self.by_name.insert(name.to_string(), value);
+        self.constants
+            .entry(value)
+            .or_insert_with(|| name.to_string());
+    }
+
+    // Drop a redefined name from the address maps
+    fn forget(&mut self, name: &str) {
+        let Some(old) = self.by_name.remove(name) else {
+            return;
+        };
+        for map in [&mut self.by_addr, &mut self.constants] {
+            if map.get(&old).map(|n| n.as_str()) == Some(name) {
+                map.remove(&old);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    // Name of the symbol exactly at `addr`
+    pub fn name_at(&self, addr: u16) -> Option<&str> {
+        self.by_addr
+            .get(&addr)
+            .or_else(|| self.constants.get(&addr))
+            .map(|n| n.as_str())
+    }
+
+    // Nearest symbol at or below `addr` and the offset from it
+    pub fn lookup(&self, addr: u16) -> Option<(&str, u16)> {
+        if let Some(name) = self.name_at(addr) {
+            return Some((name, 0));
+        }
+        self.by_addr
+            .range(..=addr)
+            .next_back()
+            .map(|(&base, name)| (name.as_str(), addr - base))
+    }
+
+    // "draw_player", "draw_player+0x6" or "0x02A4" without a symbol
+    pub fn format(&self, addr: u16) -> String {
+        match self.lookup(addr) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+{:#X}", name, offset),
+            None => format!("{:#06X}", addr),
+        }
+    }
+
+    // Like format(), but only for addresses with a symbol
+    pub fn describe(&self, addr: u16) -> Option<String> {
+        self.lookup(addr).map(|_| self.format(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_only_match_exactly() {
+        let table = SymbolTable::parse(
+            "main = 0x200\n:const draw_player 0x2A4\n:const SPRITE_HEIGHT 0x250 # a count",
+        )
+        .unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.address_of("SPRITE_HEIGHT"), Some(0x250));
+        assert_eq!(table.format(0x250), "SPRITE_HEIGHT");
+        assert_eq!(table.format(0x2A4), "draw_player");
+        // offsets are only taken from labels
+        assert_eq!(table.format(0x252), "main+0x52");
+        assert_eq!(table.format(0x2A6), "main+0xA6");
+        assert_eq!(table.describe(0x1FE), None);
+    }
+
+    #[test]
+    fn redefinitions_replace() {
+        let table = SymbolTable::parse("a = 0x300\n:const a 0x10\nb = 0x300").unwrap();
+        assert_eq!(table.address_of("a"), Some(0x10));
+        assert_eq!(table.format(0x300), "b");
+        assert_eq!(table.format(0x10), "a");
+        assert_eq!(table.format(0x11), "0x0011");
+    }
+
+    #[test]
+    fn bad_lines() {
+        for (text, line) in [
+            ("main = 0x200\nmain 0x200", 2),
+            ("1up = 3", 1),
+            ("x = 0x10000", 1),
+        ] {
+            let err = SymbolTable::parse(text).unwrap_err();
+            assert_eq!(err.line, line, "{}", err);
+        }
+    }
+}
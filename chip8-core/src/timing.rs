@@ -0,0 +1,243 @@
+/*
+   Instruction timing.
+
+   Modern modes run a fixed number of instructions per 60Hz frame (ticks_per_frame).
+   The COSMAC VIP interpreter instead spends a varying number of 1802 machine cycles
+   (8 clocks of the 1.76MHz CPU, ~4.5us) on each instruction, and a frame only has
+   room for as many as fit between two display interrupts.
+
+   The VIP costs below are approximations of the original interpreter: a fixed fetch
+   and decode overhead plus the execution time of each opcode. step() returns the cost
+   of the instruction it ran and the caller spends frame_budget() per frame. Whatever
+   the last instruction of a frame overran by is taken from the next one.
+
+   Each Quirks profile picks its timing, so any mode can run with VIP cycle costs.
+*/
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Timing {
+    // Every instruction costs 1, the budget is ticks_per_frame
+    Instructions,
+    // COSMAC VIP machine cycles
+    CosmacVip,
+}
+
+// What an instruction did, for the ones whose cost depends on it
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Outcome {
+    // Vx before the instruction ran
+    pub vx: u8,
+    pub skipped: bool,
+    // Dxyn turned off a pixel
+    pub collided: bool,
+}
+
+// Machine cycles per frame (3668) less the video DMA and interrupt routine
+pub const VIP_CYCLES_PER_FRAME: u32 = 2600;
+
+// Interpreter loop overhead paid by every instruction
+const VIP_FETCH: u32 = 40;
+
+impl Timing {
+    pub fn id(&self) -> u8 {
+        match self {
+            Timing::Instructions => 0,
+            Timing::CosmacVip => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Timing> {
+        match id {
+            0 => Some(Timing::Instructions),
+            1 => Some(Timing::CosmacVip),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timing::Instructions => "instructions",
+            Timing::CosmacVip => "vip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Timing> {
+        match name.to_lowercase().as_str() {
+            "instructions" => Some(Timing::Instructions),
+            "vip" | "cosmacvip" => Some(Timing::CosmacVip),
+            _ => None,
+        }
+    }
+
+    // Cost units available to the program each frame
+    pub fn frame_budget(&self, ticks_per_frame: u32) -> u32 {
+        match self {
+            Timing::Instructions => ticks_per_frame,
+            Timing::CosmacVip => VIP_CYCLES_PER_FRAME,
+        }
+    }
+
+    // Cost of an instruction that has just executed
+    pub fn cost(&self, opcode: u16, outcome: Outcome) -> u32 {
+        match self {
+            Timing::Instructions => 1,
+            Timing::CosmacVip => VIP_FETCH + vip_execute_cycles(opcode, outcome),
+        }
+    }
+}
+
+fn vip_execute_cycles(opcode: u16, outcome: Outcome) -> u32 {
+    let skip = if outcome.skipped { 4 } else { 0 };
+    match opcode & 0xF000 {
+        // CLS clears the 256 byte display buffer
+        0x0000 if opcode == 0x00E0 => 24 + 256 * 12,
+        0x0000 if opcode == 0x00EE => 10,
+        // 0nnn machine code routines, nothing to time
+        0x0000 => 0,
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => 10 + skip,
+        0x5000 | 0x9000 => 14 + skip,
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        // Dxy0 draws 16 rows
+        0xD000 => match get_n!(opcode) {
+            0 => vip_draw_cycles(16, outcome),
+            n => vip_draw_cycles(n as u32, outcome),
+        },
+        0xE000 => 14 + skip,
+        _ => match opcode & 0xFF {
+            0x0A => 19,
+            0x1E => 16,
+            0x29 => 16,
+            // BCD by repeated subtraction, slower for larger digits
+            0x33 => {
+                let value = outcome.vx;
+                let digits = (value / 100 + (value / 10) % 10 + value % 10) as u32;
+                80 + digits * 16
+            }
+            0x55 | 0x65 => 14 + (get_x!(opcode) as u32 + 1) * 14,
+            _ => 10,
+        },
+    }
+}
+
+/*
+   Each sprite row is shifted right by x % 8 (one 1802 shift per bit) and XORed over the
+   display byte it starts in, plus the next one when it isn't byte aligned. Rows that
+   turn pixels off take the collision branch
+*/
+fn vip_draw_cycles(rows: u32, outcome: Outcome) -> u32 {
+    let shift = (outcome.vx % 8) as u32;
+    let row = match shift {
+        0 => 34,
+        _ => 46 + shift * 4,
+    };
+    let collision = if outcome.collided { rows * 4 } else { 0 };
+    26 + rows * row + collision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8, Debugger};
+
+    fn vip(opcode: u16, outcome: Outcome) -> u32 {
+        Timing::CosmacVip.cost(opcode, outcome)
+    }
+
+    #[test]
+    fn fixed_costs() {
+        let table = [
+            (0x00E0, 3136),
+            (0x00EE, 50),
+            (0x1234, 52),
+            (0x2345, 66),
+            (0x6123, 46),
+            (0x7123, 50),
+            (0x8124, 84),
+            (0xA123, 52),
+            (0xB123, 62),
+            (0xC1FF, 76),
+            (0xF10A, 59),
+            (0xF11E, 56),
+            (0xF129, 56),
+            (0xF115, 50),
+        ];
+        for (opcode, cycles) in table {
+            assert_eq!(vip(opcode, Outcome::default()), cycles, "{:04X}", opcode);
+            assert_eq!(Timing::Instructions.cost(opcode, Outcome::default()), 1);
+        }
+        assert_eq!(Timing::CosmacVip.frame_budget(20), 2600);
+        assert_eq!(Timing::Instructions.frame_budget(20), 20);
+    }
+
+    #[test]
+    fn outcome_costs() {
+        let skipped = Outcome {
+            skipped: true,
+            ..Default::default()
+        };
+        for opcode in [0x3100, 0x4100, 0x5120, 0x9120, 0xE19E] {
+            assert_eq!(vip(opcode, skipped), vip(opcode, Outcome::default()) + 4);
+        }
+
+        // BCD: 16 cycles per unit of digit sum
+        let bcd = |vx| {
+            vip(
+                0xF133,
+                Outcome {
+                    vx,
+                    ..Default::default()
+                },
+            )
+        };
+        assert_eq!(bcd(0), 120);
+        assert_eq!(bcd(255), 120 + 12 * 16);
+
+        // save/load: 14 per register
+        assert_eq!(vip(0xF055, Outcome::default()), 68);
+        assert_eq!(vip(0xFF65, Outcome::default()), 278);
+    }
+
+    #[test]
+    fn draw_costs() {
+        let draw = |opcode, vx, collided| {
+            vip(
+                opcode,
+                Outcome {
+                    vx,
+                    collided,
+                    ..Default::default()
+                },
+            )
+        };
+        // byte aligned rows
+        assert_eq!(draw(0xD125, 8, false), 40 + 26 + 5 * 34);
+        // rows shifted by x % 8 spill into the next byte
+        assert_eq!(draw(0xD125, 3, false), 40 + 26 + 5 * (46 + 12));
+        assert_eq!(draw(0xD125, 7, false), 40 + 26 + 5 * (46 + 28));
+        // collisions cost 4 per row
+        assert_eq!(draw(0xD125, 8, true), 40 + 26 + 5 * 38);
+        // Dxy0 is timed as 16 rows
+        assert_eq!(draw(0xD120, 0, false), 40 + 26 + 16 * 34);
+    }
+
+    #[test]
+    fn frame_of_vip_cycles() {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("cosmacvip").unwrap();
+        assert_eq!(chip.quirks_mode().timing, Timing::CosmacVip);
+        // ADD V0, 1; JP 0x200 - 102 cycles a loop
+        chip.load_rom(vec![0x70, 0x01, 0x12, 0x00], 0x200).unwrap();
+        let mut debugger = Debugger::new();
+        debugger.run(&mut chip, VIP_CYCLES_PER_FRAME).unwrap();
+        assert_eq!(chip.v()[0], 26);
+        debugger.run(&mut chip, VIP_CYCLES_PER_FRAME).unwrap();
+        assert_eq!(chip.v()[0], 51);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+
+/*
+   Instruction trace, one entry per executed instruction.
+
+   Lines are fixed-width so traces from two runs (or two emulators) diff cleanly:
+
+   cycle      pc   op   instruction          I      VF  changed registers
+   0000000042 0212 8F15 SUB VF, V1           I=0202 VF=01 V1=0A VF=01
+
+   With symbols loaded address operands use their names and the line ends with the
+   location of pc, eg. "@ draw_player+0x6".
+
+   Entries either go to a writer as they happen or are kept in a ring buffer holding
+   the most recent ones. An address range limits tracing to part of the program and a
+   limit stops it after that many entries.
+*/
+
+#[derive(Clone, Debug)]
+pub struct TraceEntry {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: String,
+    pub i: u32,
+    pub vf: u8,
+    // (register, new value) for every V register the instruction changed
+    pub changed: Vec<(usize, u8)>,
+    // pc as symbol+offset, see SymbolTable
+    pub location: Option<String>,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:010} {:04X} {:04X} {:<20} I={:04X} VF={:02X}",
+            self.cycle, self.pc, self.opcode, self.instruction, self.i, self.vf
+        )?;
+        for (x, value) in self.changed.iter() {
+            write!(f, " V{:X}={:02X}", x, value)?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, " @ {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+enum Output {
+    Buffer(VecDeque<TraceEntry>, usize),
+    Writer(Box<dyn Write + Send>),
+}
+
+pub struct Tracer {
+    output: Output,
+    range: Option<(u16, u16)>,
+    limit: Option<u64>,
+    recorded: u64,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    // Keep the last `capacity` entries in memory
+    pub fn to_buffer(capacity: usize) -> Self {
+        Self::with_output(Output::Buffer(VecDeque::with_capacity(capacity), capacity))
+    }
+
+    // Write every entry as a line
+    pub fn to_writer(writer: Box<dyn Write + Send>) -> Self {
+        Self::with_output(Output::Writer(writer))
+    }
+
+    fn with_output(output: Output) -> Self {
+        Self {
+            output,
+            range: None,
+            limit: None,
+            recorded: 0,
+            error: None,
+        }
+    }
+
+    // Only trace instructions with start <= pc <= end
+    pub fn set_range(&mut self, range: Option<(u16, u16)>) {
+        self.range = range;
+    }
+
+    // Stop tracing after this many entries
+    pub fn set_limit(&mut self, limit: Option<u64>) {
+        self.limit = limit;
+    }
+
+    pub fn recorded(&self) -> u64 {
+        self.recorded
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.limit, Some(limit) if self.recorded >= limit)
+    }
+
+    // Buffered entries, oldest first. Always empty when writing to a writer
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        let buffer = match &self.output {
+            Output::Buffer(buffer, _) => Some(buffer.iter()),
+            Output::Writer(_) => None,
+        };
+        buffer.into_iter().flatten()
+    }
+
+    // The first write error, after which the tracer stops writing
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.output {
+            Output::Writer(writer) => writer.flush(),
+            Output::Buffer(..) => Ok(()),
+        }
+    }
+
+    pub(crate) fn wants(&self, pc: u16) -> bool {
+        if self.is_done() || self.error.is_some() {
+            return false;
+        }
+        match self.range {
+            Some((start, end)) => start <= pc && pc <= end,
+            None => true,
+        }
+    }
+
+    pub(crate) fn record(&mut self, entry: TraceEntry) {
+        self.recorded += 1;
+        let done = self.is_done();
+        match &mut self.output {
+            Output::Buffer(_, 0) => {}
+            Output::Buffer(buffer, capacity) => {
+                if buffer.len() == *capacity {
+                    buffer.pop_front();
+                }
+                buffer.push_back(entry);
+            }
+            Output::Writer(writer) => {
+                // nothing is written past the limit, so don't leave the tail buffered
+                let mut result = writeln!(writer, "{}", entry);
+                if done && result.is_ok() {
+                    result = writer.flush();
+                }
+                if let Err(e) = result {
+                    self.error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+    use std::sync::{Arc, Mutex};
+
+    // What reaches the file underneath the BufWriter
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn limit_flushes_the_writer() {
+        let file = Shared::default();
+        let mut tracer = Tracer::to_writer(Box::new(io::BufWriter::new(file.clone())));
+        tracer.set_limit(Some(2));
+        let mut chip = Chip8::new();
+        chip.set_tracer(Some(tracer));
+        // LD V0, 1; LD V1, 2; JP 0x204
+        chip.load_rom(vec![0x60, 0x01, 0x61, 0x02, 0x12, 0x04], 0x200)
+            .unwrap();
+        chip.step().unwrap();
+        // the first entry is still buffered
+        assert!(file.0.lock().unwrap().is_empty());
+        chip.step().unwrap();
+        let text = String::from_utf8(file.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(text.lines().count(), 2, "{}", text);
+
+        // past the limit nothing more is written
+        chip.step().unwrap();
+        assert_eq!(file.0.lock().unwrap().len(), text.len());
+    }
+}
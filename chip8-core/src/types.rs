@@ -0,0 +1,50 @@
+pub type Screen = Vec<Vec<Vec<bool>>>;
+
+pub const FONT_OFFSET: usize = 0x050;
+
+#[derive(Copy, Clone, PartialEq, Hash, Eq)]
+pub enum Key {
+    Key0 = 0x0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl TryFrom<u8> for Key {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let key = match value {
+            0x0 => Key::Key0,
+            0x1 => Key::Key1,
+            0x2 => Key::Key2,
+            0x3 => Key::Key3,
+            0x4 => Key::Key4,
+            0x5 => Key::Key5,
+            0x6 => Key::Key6,
+            0x7 => Key::Key7,
+            0x8 => Key::Key8,
+            0x9 => Key::Key9,
+            0xA => Key::A,
+            0xB => Key::B,
+            0xC => Key::C,
+            0xD => Key::D,
+            0xE => Key::E,
+            0xF => Key::F,
+            _ => return Err(value),
+        };
+        Ok(key)
+    }
+}
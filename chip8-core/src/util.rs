@@ -10,6 +10,12 @@
              kk-------
 */
 
+macro_rules! err_info {
+    () => {
+        format!("{}, line: {}", file!(), line!())
+    };
+}
+
 #[macro_export]
 macro_rules! get_x {
     ($opcode:expr) => {
@@ -43,12 +49,3 @@ macro_rules! get_kk {
         ($opcode & 0xFF) as u8
     };
 }
-
-#[macro_export]
-macro_rules! ensure_super_chip {
-    ($enabled:expr) => {
-        if !$enabled {
-            panic!("Invalid opcode - Super-Chip only");
-        }
-    };
-}
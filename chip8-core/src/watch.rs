@@ -0,0 +1,266 @@
+use crate::access::{AccessKind, MemoryAccess};
+use crate::Chip8;
+use std::fmt;
+
+/*
+   Watchpoints and register conditions for the debugger.
+
+   Watchpoint syntax:  <r|w|rw>:<start>[-<end>]        eg. "w:0x300-0x31F", "rw:0x2A4"
+   Condition syntax:   <operand> <op> <operand>        eg. "V3 == 0x10", "I >= 0x1000"
+   Breakpoint syntax:  <addr>[ if <condition>]         eg. "0x2A4", "0x2A4 if V3 == 0x10"
+
+   Operands are V0-VF, I, PC, SP, DT, ST or a number (decimal or 0x-prefixed hex),
+   ops are ==, !=, <, <=, >, >=
+*/
+
+// Wide enough for MEGA-CHIP's 24 bit I and memory
+fn parse_number(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let parsed = match s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse::<u32>(),
+    };
+    parsed.map_err(|_| format!("invalid number: {}", s))
+}
+
+// A program address, which pc keeps to 16 bits
+fn parse_pc(s: &str) -> Result<u16, String> {
+    let addr = parse_number(s)?;
+    u16::try_from(addr).map_err(|_| format!("address out of range: {}", s.trim()))
+}
+
+// "<start>[-<end>]", inclusive
+pub fn parse_range(range: &str) -> Result<(u32, u32), String> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_number(start)?, parse_number(end)?),
+        None => {
+            let addr = parse_number(range)?;
+            (addr, addr)
+        }
+    };
+    if end < start {
+        return Err(format!("empty range: {}", range));
+    }
+    Ok((start, end))
+}
+
+// parse_range() for program addresses, eg. trace ranges
+pub fn parse_pc_range(range: &str) -> Result<(u16, u16), String> {
+    let (start, end) = parse_range(range)?;
+    match (u16::try_from(start), u16::try_from(end)) {
+        (Ok(start), Ok(end)) => Ok((start, end)),
+        _ => Err(format!("address out of range: {}", range)),
+    }
+}
+
+pub fn parse_breakpoint(spec: &str) -> Result<(u16, Option<Condition>), String> {
+    match spec.split_once(" if ") {
+        Some((addr, condition)) => Ok((parse_pc(addr)?, Some(Condition::parse(condition)?))),
+        None => Ok((parse_pc(spec)?, None)),
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+    pub start: u32,
+    pub end: u32, // inclusive
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, range) = spec
+            .split_once(':')
+            .ok_or(format!("expected <r|w|rw>:<start>[-<end>], got: {}", spec))?;
+        let kind = match kind.trim().to_lowercase().as_str() {
+            "r" => WatchKind::Read,
+            "w" => WatchKind::Write,
+            "rw" => WatchKind::ReadWrite,
+            k => return Err(format!("invalid watch kind: {}", k)),
+        };
+        let (start, end) = parse_range(range)?;
+        Ok(Self { start, end, kind })
+    }
+
+    pub fn matches(&self, access: &MemoryAccess) -> bool {
+        let kind_matches = matches!(
+            (self.kind, access.kind),
+            (WatchKind::ReadWrite, _)
+                | (WatchKind::Read, AccessKind::Read)
+                | (WatchKind::Write, AccessKind::Write)
+        );
+        kind_matches && access.overlaps(self.start as usize, self.end as usize)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "r",
+            WatchKind::Write => "w",
+            WatchKind::ReadWrite => "rw",
+        };
+        write!(f, "{}:0x{:04X}-0x{:04X}", kind, self.start, self.end)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Operand {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Value(u32),
+}
+
+impl Operand {
+    fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let upper = s.to_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "PC" => Operand::Pc,
+            "SP" => Operand::Sp,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            _ if upper.len() == 2 && upper.starts_with('V') => {
+                let x = usize::from_str_radix(&upper[1..], 16)
+                    .map_err(|_| format!("invalid register: {}", s))?;
+                Operand::V(x)
+            }
+            _ => Operand::Value(parse_number(s)?),
+        };
+        Ok(operand)
+    }
+
+    fn value(&self, chip: &Chip8) -> u32 {
+        match *self {
+            Operand::V(x) => chip.v()[x] as u32,
+            Operand::I => chip.i(),
+            Operand::Pc => chip.pc() as u32,
+            Operand::Sp => chip.sp() as u32,
+            Operand::Dt => chip.dt() as u32,
+            Operand::St => chip.st() as u32,
+            Operand::Value(v) => v,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+pub struct Condition {
+    lhs: Operand,
+    op: CmpOp,
+    rhs: Operand,
+    text: String,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // two character operators first so "<=" isn't read as "<"
+        let ops = [
+            ("==", CmpOp::Eq),
+            ("!=", CmpOp::Ne),
+            ("<=", CmpOp::Le),
+            (">=", CmpOp::Ge),
+            ("<", CmpOp::Lt),
+            (">", CmpOp::Gt),
+        ];
+        for (token, op) in ops {
+            if let Some((lhs, rhs)) = text.split_once(token) {
+                return Ok(Self {
+                    lhs: Operand::parse(lhs)?,
+                    op,
+                    rhs: Operand::parse(rhs)?,
+                    text: text.trim().to_string(),
+                });
+            }
+        }
+        Err(format!("expected <operand> <op> <operand>, got: {}", text))
+    }
+
+    pub fn eval(&self, chip: &Chip8) -> bool {
+        let lhs = self.lhs.value(chip);
+        let rhs = self.rhs.value(chip);
+        match self.op {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Debugger, StopReason};
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("0x300-0x31F"), Ok((0x300, 0x31F)));
+        assert_eq!(parse_range("0x100000"), Ok((0x100000, 0x100000)));
+        assert!(parse_range("0x31F-0x300").is_err());
+        assert_eq!(parse_pc_range("0x200-0x2FF"), Ok((0x200, 0x2FF)));
+        assert!(parse_pc_range("0x200-0x10000").is_err());
+        assert!(parse_breakpoint("0x10000").is_err());
+        let (addr, condition) = parse_breakpoint("0x2A4 if I >= 0x100000").unwrap();
+        assert_eq!(addr, 0x2A4);
+        assert!(condition.is_some());
+    }
+
+    #[test]
+    fn watch_megachip_memory() {
+        let mut chip = Chip8::new();
+        chip.set_core_mode("megachip").unwrap();
+        let rom = vec![
+            0x01, 0x10, 0x00, 0x04, // LDHI I, 0x100004
+            0x60, 0x2A, // LD V0, 0x2A
+            0xF0, 0x55, // LD [I], V0
+            0x12, 0x08, // JP 0x208
+        ];
+        chip.load_rom(rom, 0x200).unwrap();
+
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Watchpoint::parse("w:0x100000-0x10000F").unwrap());
+        debugger.add_condition(Condition::parse("I == 0x100004").unwrap());
+        let stop = debugger.run(&mut chip, 100).unwrap();
+        // the condition holds as soon as I is loaded
+        let held = matches!(stop, Some(StopReason::Condition { .. }));
+        assert!(held, "{:?}", stop);
+
+        debugger.clear_conditions();
+        debugger.resume();
+        let stop = debugger.run(&mut chip, 100).unwrap();
+        let Some(StopReason::Watchpoint { pc, access, .. }) = stop else {
+            panic!("{:?}", stop);
+        };
+        assert_eq!((pc, access.addr), (0x206, 0x100004));
+    }
+}
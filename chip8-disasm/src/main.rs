@@ -0,0 +1,191 @@
+use chip8_core::disasm::{decode_at, CodeMap};
+use chip8_core::{Mode, Quirks, SymbolTable};
+use std::{env, fs, process};
+
+// Data bytes per DB line
+const DATA_PER_LINE: usize = 8;
+
+struct Args {
+    rom_file: String,
+    mode: Mode,
+    linear: bool,
+    symbols_file: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        return Err("Missing arguments".to_string());
+    }
+    let mode = match args[2].as_str() {
+        "1" => Mode::Chip8Modern,
+        "2" => Mode::SuperChipModern,
+        "3" => Mode::SuperChipLegacy,
+        "4" => Mode::XoChip,
+        "5" => Mode::CosmacVip,
+        "6" => Mode::Chip8X,
+        "7" => Mode::MegaChip,
+        "8" => Mode::HiresChip8,
+        "9" => Mode::TwoPage,
+        "10" => Mode::SuperChip10,
+        m => return Err(format!("Invalid Core Mode: {}", m)),
+    };
+
+    let mut parsed = Args {
+        rom_file: args[1].clone(),
+        mode,
+        linear: false,
+        symbols_file: None,
+    };
+    let mut rest = args[3..].iter();
+    while let Some(flag) = rest.next() {
+        match flag.as_str() {
+            "--linear" => parsed.linear = true,
+            "--symbols" => {
+                let value = rest.next().ok_or(format!("Missing value for {}", flag))?;
+                parsed.symbols_file = Some(value.clone());
+            }
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("Error: {}\n\n", e);
+            usage();
+            process::exit(1);
+        }
+    };
+
+    let rom = match fs::read(&args.rom_file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Error loading ROM: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let symbols = match &args.symbols_file {
+        Some(file) => {
+            let symbols = fs::read_to_string(file)
+                .map_err(|e| e.to_string())
+                .and_then(|text| SymbolTable::parse(&text).map_err(|e| e.to_string()));
+            match symbols {
+                Ok(symbols) => symbols,
+                Err(e) => {
+                    eprintln!("Error loading symbols from {}: {}", file, e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => SymbolTable::new(),
+    };
+
+    // Where the ROM is loaded and starts executing
+    let base = args.mode.program_start();
+    let map = CodeMap::analyze(&rom, base, base, args.mode);
+    let mode_label = Quirks::new(args.mode).mode_label;
+    println!("; {} - {} bytes, {}", args.rom_file, rom.len(), mode_label);
+
+    let mut offset = 0;
+    let mut data: Vec<u8> = vec![];
+    let mut data_addr = base;
+    while offset < rom.len() {
+        let addr = base + offset as u16;
+        let is_code = args.linear || map.is_code(addr);
+        let instruction = if is_code {
+            decode_at(&rom, offset as u16, args.mode)
+        } else {
+            None
+        };
+
+        let label = label_at(addr, &map, &symbols);
+        let Some(instruction) = instruction else {
+            if data.is_empty() || label.is_some() {
+                print_data(data_addr, &data);
+                data.clear();
+                data_addr = addr;
+            }
+            if let Some(label) = &label {
+                println!("{}:", label);
+            }
+            data.push(rom[offset]);
+            if data.len() == DATA_PER_LINE {
+                print_data(data_addr, &data);
+                data.clear();
+                data_addr = addr + 1;
+            }
+            offset += 1;
+            continue;
+        };
+
+        print_data(data_addr, &data);
+        data.clear();
+        if let Some(label) = &label {
+            println!("{}:", label);
+        }
+        let raw = match instruction.operand {
+            Some(operand) => format!("{:04X} {:04X}", instruction.opcode, operand),
+            None => format!("{:04X}", instruction.opcode),
+        };
+        let note = if instruction.valid_in(args.mode) {
+            String::new()
+        } else {
+            format!("  ; not valid in {}", mode_label)
+        };
+        println!(
+            "{:#06X}  {:<10}  {}{}",
+            addr,
+            raw,
+            instruction.symbolic(&symbols),
+            note
+        );
+        offset += instruction.size() as usize;
+    }
+    print_data(data_addr, &data);
+}
+
+// Symbol names take the place of generated Lnnn labels
+fn label_at(addr: u16, map: &CodeMap, symbols: &SymbolTable) -> Option<String> {
+    match symbols.name_at(addr) {
+        Some(name) => Some(name.to_string()),
+        None if map.is_label(addr) => Some(format!("L{:03X}", addr)),
+        None => None,
+    }
+}
+
+fn print_data(addr: u16, data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    let bytes = data
+        .iter()
+        .map(|b| format!("0x{:02X}", b))
+        .collect::<Vec<String>>()
+        .join(", ");
+    println!("{:#06X}  {:<10}  DB {}", addr, "", bytes);
+}
+
+fn usage() {
+    let args: Vec<String> = env::args().collect();
+    eprintln!("Usage: {} <Filename> <CHIP Mode> [--linear] [--symbols <File>]", args[0]);
+    eprintln!();
+    eprintln!("<Filename> - path to ROM File");
+    eprintln!("<CHIP Mode>");
+    eprintln!("\t1 - CHIP-8");
+    eprintln!("\t2 - SuperChip Modern");
+    eprintln!("\t3 - SuperChip Legacy (SCHIP 1.1)");
+    eprintln!("\t4 - XO-Chip");
+    eprintln!("\t5 - COSMAC VIP");
+    eprintln!("\t6 - CHIP-8X (loaded at 0x300)");
+    eprintln!("\t7 - MEGA-CHIP");
+    eprintln!("\t8 - HIRES CHIP-8 (1260 at 0x200 starts the program at 0x2C0)");
+    eprintln!("\t9 - CHIP-8 two-page display");
+    eprintln!("\t10 - SuperChip 1.0");
+    eprintln!("--linear - decode every word as an instruction instead of following code flow");
+    eprintln!("--symbols <File> - symbol map ('name = 0x2A4' lines) used for labels and operands\n");
+}
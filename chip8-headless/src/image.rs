@@ -0,0 +1,98 @@
+use chip8_core::megachip::{MegaScreen, MEGA_COLS, MEGA_ROWS};
+use chip8_core::types::Screen;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// Default XO-CHIP palette, indexed by the combined bit plane value of a pixel
+const PALETTE: [u32; 16] = [
+    0x000000, 0xC6C6C6, 0x828282, 0x515151, 0xFF0000, 0xFF7F00, 0xFFFF00, 0x7FFF00, 0x00FF00,
+    0x00FF7F, 0x00FFFF, 0x007FFF, 0x0000FF, 0x7F00FF, 0xFF00FF, 0xFF007F,
+];
+
+fn pixel_index(layers: &[bool]) -> u8 {
+    layers
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, &on)| if on { acc | (1 << i) } else { acc })
+}
+
+pub fn write_screen(screen: &Screen, filename: &str) -> io::Result<()> {
+    if filename.to_lowercase().ends_with(".png") {
+        write_png(screen, filename)
+    } else {
+        write_pbm(screen, filename)
+    }
+}
+
+// The MEGA-CHIP frame: RGB PNG, or PBM with every pixel that isn't black set
+pub fn write_mega(mega: &MegaScreen, filename: &str) -> io::Result<()> {
+    let frame = mega.frame();
+    let mut w = BufWriter::new(File::create(filename)?);
+    if !filename.to_lowercase().ends_with(".png") {
+        writeln!(w, "P1\n{} {}", MEGA_COLS, MEGA_ROWS)?;
+        for row in frame.chunks(MEGA_COLS) {
+            let line = row
+                .iter()
+                .map(|&c| if c == 0 { "0" } else { "1" })
+                .collect::<Vec<&str>>()
+                .join(" ");
+            writeln!(w, "{}", line)?;
+        }
+        return w.flush();
+    }
+
+    let mut encoder = png::Encoder::new(w, MEGA_COLS as u32, MEGA_ROWS as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = frame
+        .iter()
+        .flat_map(|c| [(c >> 16) as u8, (c >> 8) as u8, *c as u8])
+        .collect();
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+// Plain (P1) PBM - a pixel is black if it's set on any bit plane
+pub fn write_pbm(screen: &Screen, filename: &str) -> io::Result<()> {
+    let rows = screen.len();
+    let cols = screen.first().map_or(0, |r| r.len());
+    let mut w = BufWriter::new(File::create(filename)?);
+    writeln!(w, "P1\n{} {}", cols, rows)?;
+    for row in screen.iter() {
+        let line = row
+            .iter()
+            .map(|c| match pixel_index(c) {
+                0 => "0",
+                _ => "1",
+            })
+            .collect::<Vec<&str>>()
+            .join(" ");
+        writeln!(w, "{}", line)?;
+    }
+    w.flush()
+}
+
+// Indexed PNG using the default XO-CHIP palette
+pub fn write_png(screen: &Screen, filename: &str) -> io::Result<()> {
+    let rows = screen.len();
+    let cols = screen.first().map_or(0, |r| r.len());
+    let w = BufWriter::new(File::create(filename)?);
+
+    let mut encoder = png::Encoder::new(w, cols as u32, rows as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    let palette: Vec<u8> = PALETTE
+        .iter()
+        .flat_map(|c| [(c >> 16) as u8, (c >> 8) as u8, *c as u8])
+        .collect();
+    encoder.set_palette(palette);
+
+    let data: Vec<u8> = screen
+        .iter()
+        .flat_map(|row| row.iter().map(|c| pixel_index(c)))
+        .collect();
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
@@ -0,0 +1,86 @@
+use chip8_core::types::Key;
+use std::fs;
+
+/*
+   Key schedule file format, one event per line:
+
+       <frame> <key> <down|up>
+
+   <frame> is the 0-based frame number the event is applied on (before that frame runs),
+   <key> is a hex digit 0-F. Blank lines and lines starting with '#' are ignored.
+
+   Example - tap key 5 for 3 frames starting at frame 120:
+       120 5 down
+       123 5 up
+*/
+
+pub struct KeyEvent {
+    pub frame: u32,
+    pub key: Key,
+    pub pressed: bool,
+}
+
+pub struct KeySchedule {
+    events: Vec<KeyEvent>,
+    next: usize,
+}
+
+impl KeySchedule {
+    pub fn empty() -> Self {
+        Self {
+            events: vec![],
+            next: 0,
+        }
+    }
+
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = vec![];
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 3 {
+                return Err(format!(
+                    "line {}: expected '<frame> <key> <down|up>'",
+                    line_num + 1
+                ));
+            }
+            let frame = parts[0]
+                .parse::<u32>()
+                .map_err(|e| format!("line {}: invalid frame: {}", line_num + 1, e))?;
+            let key = u8::from_str_radix(parts[1], 16)
+                .ok()
+                .and_then(|k| Key::try_from(k).ok())
+                .ok_or(format!("line {}: invalid key: {}", line_num + 1, parts[1]))?;
+            let pressed = match parts[2] {
+                "down" => true,
+                "up" => false,
+                s => return Err(format!("line {}: invalid key state: {}", line_num + 1, s)),
+            };
+            events.push(KeyEvent {
+                frame,
+                key,
+                pressed,
+            });
+        }
+        // stable sort keeps same-frame events in file order
+        events.sort_by_key(|e| e.frame);
+        Ok(Self { events, next: 0 })
+    }
+
+    // Returns every event scheduled for `frame` that hasn't been applied yet
+    pub fn events_for_frame(&mut self, frame: u32) -> &[KeyEvent] {
+        let start = self.next;
+        while self.next < self.events.len() && self.events[self.next].frame <= frame {
+            self.next += 1;
+        }
+        &self.events[start..self.next]
+    }
+}
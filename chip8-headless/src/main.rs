@@ -0,0 +1,352 @@
+use chip8_core::font::Font;
+use chip8_core::rng::DEFAULT_SEED;
+use chip8_core::watch::parse_pc_range;
+use chip8_core::{Chip8, Mode, Quirks, Rng, RngKind, StepResult, SymbolTable, Timing, Tracer};
+use std::io::{self, BufWriter};
+use std::{env, fs, process};
+
+mod image;
+mod keys;
+
+use keys::KeySchedule;
+
+struct Args {
+    rom_file: String,
+    core_mode: String,
+    ticks_per_frame: u32,
+    frames: u32,
+    keys_file: Option<String>,
+    out_file: Option<String>,
+    rng_kind: RngKind,
+    rng_seed: u32,
+    timing: Option<Timing>,
+    profile_file: Option<String>,
+    quirks: Vec<String>,
+    trace_file: Option<String>,
+    trace_range: Option<(u16, u16)>,
+    trace_limit: Option<u64>,
+    symbols_file: Option<String>,
+    font_file: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 5 {
+        return Err("Missing arguments".to_string());
+    }
+    let core_mode = match args[2].as_str() {
+        "1" => "chip8",
+        "2" => "superchipmodern",
+        "3" => "superchiplegacy",
+        "4" => "xochip",
+        "5" => "cosmacvip",
+        "6" => "chip8x",
+        "7" => "megachip",
+        "8" => "hireschip8",
+        "9" => "twopage",
+        "10" => "superchip10",
+        m => return Err(format!("Invalid Core Mode: {}", m)),
+    };
+    let ticks_per_frame = args[3]
+        .parse::<u32>()
+        .map_err(|e| format!("Invalid tick rate: {}", e))?;
+    let frames = args[4]
+        .parse::<u32>()
+        .map_err(|e| format!("Invalid frame count: {}", e))?;
+
+    let mut parsed = Args {
+        rom_file: args[1].clone(),
+        core_mode: core_mode.to_string(),
+        ticks_per_frame,
+        frames,
+        keys_file: None,
+        out_file: None,
+        rng_kind: RngKind::Xorshift,
+        rng_seed: DEFAULT_SEED,
+        timing: None,
+        profile_file: None,
+        quirks: vec![],
+        trace_file: None,
+        trace_range: None,
+        trace_limit: None,
+        symbols_file: None,
+        font_file: None,
+    };
+
+    let mut rest = args[5..].iter();
+    while let Some(flag) = rest.next() {
+        let value = rest
+            .next()
+            .ok_or(format!("Missing value for {}", flag))?
+            .clone();
+        match flag.as_str() {
+            "--keys" => parsed.keys_file = Some(value),
+            "--out" => parsed.out_file = Some(value),
+            "--seed" => {
+                parsed.rng_seed = value
+                    .parse::<u32>()
+                    .map_err(|e| format!("Invalid seed: {}", e))?
+            }
+            "--rng" => {
+                parsed.rng_kind =
+                    RngKind::from_name(&value).ok_or(format!("Invalid RNG: {}", value))?
+            }
+            "--profile" => parsed.profile_file = Some(value),
+            "--quirk" => {
+                Quirks::new(Mode::Chip8Modern)
+                    .apply(&value)
+                    .map_err(|e| format!("Invalid quirk: {}", e))?;
+                parsed.quirks.push(value);
+            }
+            "--timing" => {
+                parsed.timing =
+                    Some(Timing::from_name(&value).ok_or(format!("Invalid timing: {}", value))?)
+            }
+            "--trace" => parsed.trace_file = Some(value),
+            "--trace-range" => {
+                parsed.trace_range =
+                    Some(parse_pc_range(&value).map_err(|e| format!("Invalid trace range: {}", e))?)
+            }
+            "--trace-limit" => {
+                parsed.trace_limit = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|e| format!("Invalid trace limit: {}", e))?,
+                )
+            }
+            "--symbols" => parsed.symbols_file = Some(value),
+            // a built in font is just the font quirk
+            "--font" => match Font::from_name(&value) {
+                Some(font) => parsed.quirks.push(format!("font={}", font.name())),
+                None => parsed.font_file = Some(value),
+            },
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("Error: {}\n\n", e);
+            usage();
+            process::exit(1);
+        }
+    };
+
+    let rom = match fs::read(&args.rom_file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Error loading ROM: {}", e);
+            process::exit(1);
+        }
+    };
+    let mut schedule = match &args.keys_file {
+        Some(f) => match KeySchedule::load(f) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error loading key schedule: {}", e);
+                process::exit(1);
+            }
+        },
+        None => KeySchedule::empty(),
+    };
+
+    let mut chip = Chip8::new();
+    if let Err(e) = chip.set_core_mode(&args.core_mode) {
+        eprintln!("Error: {}", e.error_type);
+        process::exit(1);
+    }
+    let mut quirks = chip.quirks_mode().clone();
+    if let Some(profile_file) = &args.profile_file {
+        let profile = fs::read_to_string(profile_file)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Quirks::parse_profile(&text));
+        match profile {
+            Ok(profile) => quirks = profile,
+            Err(e) => {
+                eprintln!("Error loading profile {}: {}", profile_file, e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(timing) = args.timing {
+        quirks.timing = timing;
+    }
+    for spec in args.quirks.iter() {
+        // parse_args only checked it against the CHIP-8 preset
+        if let Err(e) = quirks.apply(spec) {
+            eprintln!("Error: Invalid quirk: {}\n\n", e);
+            usage();
+            process::exit(1);
+        }
+    }
+    chip.set_quirks_mode(quirks);
+    if let Some(font_file) = &args.font_file {
+        let font = fs::read(font_file)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                chip.set_custom_font(Some(&bytes))
+                    .map_err(|e| e.error_type.to_string())
+            });
+        if let Err(e) = font {
+            eprintln!("Error loading font from {}: {}", font_file, e);
+            process::exit(1);
+        }
+    }
+    chip.set_rng(Rng::new(args.rng_kind, args.rng_seed));
+    let start = chip.quirks_mode().mode.program_start();
+    if let Err(e) = chip.load_rom(rom, start) {
+        eprintln!("Error loading ROM bytes: {}", e);
+        process::exit(1);
+    }
+
+    if let Some(symbols_file) = &args.symbols_file {
+        let symbols = fs::read_to_string(symbols_file)
+            .map_err(|e| e.to_string())
+            .and_then(|text| SymbolTable::parse(&text).map_err(|e| e.to_string()));
+        match symbols {
+            Ok(symbols) => chip.set_symbols(symbols),
+            Err(e) => {
+                eprintln!("Error loading symbols from {}: {}", symbols_file, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(trace_file) = &args.trace_file {
+        let writer: Box<dyn io::Write + Send> = if trace_file == "-" {
+            Box::new(io::stdout())
+        } else {
+            match fs::File::create(trace_file) {
+                Ok(f) => Box::new(BufWriter::new(f)),
+                Err(e) => {
+                    eprintln!("Error creating {}: {}", trace_file, e);
+                    process::exit(1);
+                }
+            }
+        };
+        let mut tracer = Tracer::to_writer(writer);
+        tracer.set_range(args.trace_range);
+        tracer.set_limit(args.trace_limit);
+        chip.set_tracer(Some(tracer));
+    }
+
+    let mut exit_code = 0;
+    // cost the last frame overran its budget by, see Timing
+    let mut overrun = 0;
+    'frames: for frame in 0..args.frames {
+        for event in schedule.events_for_frame(frame) {
+            chip.set_key_state(event.key, event.pressed);
+        }
+
+        chip.v_blank();
+        let budget = chip.quirks_mode().timing.frame_budget(args.ticks_per_frame);
+        let mut spent = overrun;
+        while spent < budget && !chip.is_blocked() {
+            match chip.step() {
+                Ok(StepResult::Ran(cost)) => spent += cost,
+                Ok(StepResult::Exited) => {
+                    // a clean end of the run, same as running out of frames
+                    println!("Exited at frame {}", frame);
+                    report_warnings(&mut chip, frame);
+                    break 'frames;
+                }
+                Err(e) => {
+                    eprintln!("Error at frame {}: {}", frame, e);
+                    if let Some(name) = e.error_type.pc().and_then(|pc| chip.symbols().describe(pc))
+                    {
+                        eprintln!("PC location: {}", name);
+                    }
+                    report_warnings(&mut chip, frame);
+                    exit_code = 1;
+                    break 'frames;
+                }
+            }
+        }
+        overrun = spent.saturating_sub(budget);
+        chip.tick_timers();
+        report_warnings(&mut chip, frame);
+    }
+
+    if let Some(tracer) = chip.tracer_mut() {
+        let result = match tracer.error() {
+            Some(e) => Err(e.to_string()),
+            None => tracer.flush().map_err(|e| e.to_string()),
+        };
+        if let Err(e) = result {
+            eprintln!("Error writing trace: {}", e);
+            exit_code = 1;
+        }
+    }
+
+    if let Some(out_file) = &args.out_file {
+        let screen = chip.get_screen();
+        let screen = screen.lock().unwrap();
+        let result = match chip.mega_screen() {
+            Some(mega) => image::write_mega(mega, out_file),
+            None => image::write_screen(&screen, out_file),
+        };
+        if let Err(e) = result {
+            eprintln!("Error writing {}: {}", out_file, e);
+            process::exit(1);
+        }
+    }
+    println!("{}", chip.get_state());
+
+    process::exit(exit_code);
+}
+
+// Platform violations with platform_check=warn, see PlatformCheck
+fn report_warnings(chip: &mut Chip8, frame: u32) {
+    for warning in chip.take_warnings() {
+        eprintln!("Warning at frame {}: {}", frame, warning.error_type);
+        if let Some(name) = warning
+            .error_type
+            .pc()
+            .and_then(|pc| chip.symbols().describe(pc))
+        {
+            eprintln!("PC location: {}", name);
+        }
+    }
+}
+
+fn usage() {
+    let args: Vec<String> = env::args().collect();
+    eprintln!(
+        "Usage: {} <Filename> <CHIP Mode> <Ticks-per-frame> <Frames> [--keys <File>] [--out <File>] [--seed <N>] [--rng <xorshift|vip>] [--timing <instructions|vip>] [--profile <File>] [--quirk <Name>=<Value>] [--trace <File>] [--trace-range <Start>-<End>] [--trace-limit <N>] [--symbols <File>] [--font <Name|File>]",
+        args[0]
+    );
+    eprintln!();
+    eprintln!("<Filename> - path to ROM File");
+    eprintln!("<CHIP Mode>");
+    eprintln!("\t1 - CHIP-8");
+    eprintln!("\t2 - SuperChip Modern");
+    eprintln!("\t3 - SuperChip Legacy (SCHIP 1.1)");
+    eprintln!("\t4 - XO-Chip");
+    eprintln!("\t5 - COSMAC VIP (original timing, <Ticks-per-frame> is ignored)");
+    eprintln!("\t6 - CHIP-8X (VIP timing, loaded at 0x300)");
+    eprintln!("\t7 - MEGA-CHIP");
+    eprintln!("\t8 - HIRES CHIP-8 (VIP timing, 64x64 after 1260 at 0x200)");
+    eprintln!("\t9 - CHIP-8 two-page display (VIP timing, 64x64)");
+    eprintln!("\t10 - SuperChip 1.0");
+    eprintln!("<Ticks-per-frame> - Number of instructions emulated per frame");
+    eprintln!("<Frames> - Number of 60Hz frames to run before exiting");
+    eprintln!("--keys <File> - key schedule, one '<frame> <key> <down|up>' event per line");
+    eprintln!("--out <File> - write the final screen as PNG (*.png) or PBM (anything else)");
+    eprintln!("--seed <N> - seed for the Cxkk random number generator (default: fixed)");
+    eprintln!("--rng <xorshift|vip> - Cxkk random number generator");
+    eprintln!("--timing <instructions|vip> - run <Ticks-per-frame> instructions or COSMAC VIP machine cycles per frame (default: per mode)");
+    eprintln!("--profile <File> - quirk profile to use instead of the <CHIP Mode> preset");
+    eprintln!("--quirk <Name>=<Value> - set one quirk, eg. 'vf_reset=false' (repeatable)");
+    eprintln!("--trace <File> - write every executed instruction to a file ('-' for stdout)");
+    eprintln!("--trace-range <Start>-<End> - only trace instructions in this address range");
+    eprintln!("--trace-limit <N> - stop tracing after N instructions");
+    eprintln!("--symbols <File> - symbol map ('name = 0x2A4' lines) for traces and errors");
+    eprintln!("--font <Name|File> - vip, dream6800, eti660, fishnchips, schip, octo or a font file (default: per mode)");
+    eprintln!();
+    eprintln!("Runs until <Frames> have passed or the program exits with 00FD. Exits with 0");
+    eprintln!("then, or 1 on a core error or a file that couldn't be read or written.\n");
+}
@@ -1,4 +1,5 @@
 use crate::config::Config;
+use chip8_core::megachip::Sample;
 use std::sync::{Arc, Mutex, RwLock};
 use tinyaudio::{run_output_device, BaseAudioOutputDevice, OutputDeviceParameters};
 
@@ -30,14 +31,60 @@ impl SquareWave {
     }
 }
 
+// MEGA-CHIP sampled sound, played over the square wave regardless of the sound timer
+pub struct SamplePlayer {
+    sample: Option<Sample>,
+    pos: f64,  // index into the sample data
+    step: f64, // sample rate / device_sample_rate
+}
+
+impl SamplePlayer {
+    pub fn new() -> Self {
+        Self {
+            sample: None,
+            pos: 0.0,
+            step: 0.0,
+        }
+    }
+
+    pub fn play(&mut self, sample: Sample) {
+        self.step = sample.rate as f64 / 44100.0;
+        self.pos = 0.0;
+        self.sample = Some(sample);
+    }
+
+    pub fn stop(&mut self) {
+        self.sample = None;
+    }
+
+    // Next output level from -1.0 to 1.0, None once nothing is playing
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.sample.as_ref()?;
+        let len = sample.data.len();
+        if self.pos as usize >= len {
+            if !sample.looping || len == 0 {
+                self.sample = None;
+                return None;
+            }
+            self.pos = 0.0;
+        }
+        let sample = self.sample.as_ref()?;
+        let level = sample.data[self.pos as usize] as f32 / 128.0 - 1.0;
+        self.pos += self.step;
+        Some(level)
+    }
+}
+
 pub fn init_audio(
     global_square_wave: &Arc<Mutex<SquareWave>>,
+    global_sample_player: &Arc<Mutex<SamplePlayer>>,
     global_config: &Arc<Mutex<Config>>,
     silence: &Arc<RwLock<bool>>,
 ) -> Option<Box<dyn BaseAudioOutputDevice>> {
-    let sw_handle = Arc::clone(&global_square_wave);
-    let audio_config_handle = Arc::clone(&global_config);
-    let silence_reader = Arc::clone(&silence);
+    let sw_handle = Arc::clone(global_square_wave);
+    let sample_handle = Arc::clone(global_sample_player);
+    let audio_config_handle = Arc::clone(global_config);
+    let silence_reader = Arc::clone(silence);
     let params = OutputDeviceParameters {
         channels_count: 1,
         sample_rate: 44100,
@@ -50,32 +97,36 @@ pub fn init_audio(
             let paused = c.pause_emulation;
             let audio_level = c.audio_level;
             drop(c);
-            if paused || *(silence_reader.read().unwrap()) {
+            if paused {
                 for d in data {
                     *d = 0.0;
                 }
                 return;
             }
+            let silent = *(silence_reader.read().unwrap());
 
             for samples in data.chunks_mut(params.channels_count) {
                 for sample in samples {
-                    let mut sw = sw_handle.lock().unwrap();
-                    *sample = if sw.bit_pattern[(sw.phase_bit + 0.5) as usize] {
-                        audio_level
-                    } else {
-                        -audio_level
-                    };
-                    sw.phase_bit += sw.phase_inc;
-                    if (sw.phase_bit + 0.5) as usize >= 128 {
-                        sw.phase_bit = 0.0;
+                    *sample = 0.0;
+                    if !silent {
+                        let mut sw = sw_handle.lock().unwrap();
+                        *sample = if sw.bit_pattern[(sw.phase_bit + 0.5) as usize] {
+                            audio_level
+                        } else {
+                            -audio_level
+                        };
+                        sw.phase_bit += sw.phase_inc;
+                        if (sw.phase_bit + 0.5) as usize >= 128 {
+                            sw.phase_bit = 0.0;
+                        }
+                    }
+                    if let Some(level) = sample_handle.lock().unwrap().next() {
+                        *sample = (*sample + level * audio_level).clamp(-1.0, 1.0);
                     }
                 }
             }
         }
     });
 
-    match device {
-        Ok(d) => Some(d),
-        Err(_) => None,
-    }
+    device.ok()
 }
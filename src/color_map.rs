@@ -36,14 +36,9 @@ impl ColorMap {
 }
 
 impl ColorMap {    
-    pub fn set_int_color_map(&mut self, int_color_map: &Vec<u32>) {
+    pub fn set_int_color_map(&mut self, int_color_map: &[u32]) {
         self.custom_map = int_color_map.iter()
-            .map(|c| {
-                let r = ((c >> 16) & 0xFFu32) as f32 / 255.0;
-                let g = ((c >> 8) & 0xFFu32) as f32 / 255.0;
-                let b = ((c) & 0xFFu32) as f32 / 255.0;
-                Color::new(r, g, b, 1.0)
-            })
+            .map(|c| from_rgb(*c))
             .collect();
     }
     
@@ -56,3 +51,11 @@ impl ColorMap {
         }
     }
 }
+
+// 0xRRGGBB
+pub fn from_rgb(c: u32) -> Color {
+    let r = ((c >> 16) & 0xFFu32) as f32 / 255.0;
+    let g = ((c >> 8) & 0xFFu32) as f32 / 255.0;
+    let b = ((c) & 0xFFu32) as f32 / 255.0;
+    Color::new(r, g, b, 1.0)
+}
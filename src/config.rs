@@ -8,6 +8,39 @@ pub struct Config {
     pub ticks_per_frame: u32,
     pub color_map: Vec<u32>,
     pub audio_level: f32,
+    pub rewind_seconds: u32,
+    pub rewind_budget_kb: u32,
+    pub rng: String,
+    pub rng_seed: Option<u32>,
+    // "instructions" or "vip", None uses the mode's own timing
+    pub timing: Option<String>,
+    // quirk profile name (or file on desktop) replacing the core_mode preset
+    pub profile: Option<String>,
+    // "<quirk>=<value>" overrides applied on top
+    pub quirks: Vec<String>,
+    // save the resulting quirks as a profile with this name
+    pub save_profile: Option<String>,
+    // look the ROM up in the ROM database and take its mode, quirks, tickrate and colors
+    pub rom_db: bool,
+    // database file in place of the built-in one (desktop only)
+    pub rom_db_file: Option<String>,
+    pub break_on_error: bool,
+    // "<addr>[ if <condition>]", "<r|w|rw>:<start>[-<end>]" and "<condition>" specs
+    pub breakpoints: Vec<String>,
+    pub watchpoints: Vec<String>,
+    pub break_conditions: Vec<String>,
+    // the ROM bytes are Octo source to be assembled on load
+    pub source: bool,
+    // instruction trace (desktop only), range as "<start>-<end>"
+    pub trace_file: Option<String>,
+    pub trace_range: Option<String>,
+    pub trace_limit: Option<u64>,
+    // symbol map shown in the debugger, traces and errors (desktop only)
+    pub symbols_file: Option<String>,
+    // font file loaded over the quirks' font (desktop only)
+    pub font_file: Option<String>,
+    // GDB remote protocol server on 127.0.0.1 (desktop only)
+    pub gdb_port: Option<u16>,
 }
 
 fn rgb_to_int(r: f32, g: f32, b: f32) -> u32 {
@@ -25,6 +58,27 @@ impl Config {
             core_mode: "xo-chip".to_string(),
             ticks_per_frame: 100000,
             audio_level: 0.1,
+            rewind_seconds: 30,
+            rewind_budget_kb: 32 * 1024,
+            rng: "xorshift".to_string(),
+            rng_seed: None,
+            timing: None,
+            profile: None,
+            quirks: vec![],
+            save_profile: None,
+            rom_db: true,
+            rom_db_file: None,
+            break_on_error: false,
+            breakpoints: vec![],
+            watchpoints: vec![],
+            break_conditions: vec![],
+            source: false,
+            trace_file: None,
+            trace_range: None,
+            trace_limit: None,
+            symbols_file: None,
+            font_file: None,
+            gdb_port: None,
             color_map: vec![
                 rgb_to_int(0.0, 0.0, 0.0),
                 rgb_to_int(0.78, 0.78, 0.78),
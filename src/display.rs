@@ -1,12 +1,16 @@
-use crate::core::error::CoreError;
-use crate::core::quirks::Quirks;
-use crate::core::types::Screen;
+use chip8_core::chip8x::{self, ColorBoard};
+use chip8_core::disasm;
+use chip8_core::megachip::{MegaScreen, MEGA_COLS, MEGA_ROWS};
+use chip8_core::types::Screen;
+use chip8_core::{Chip8, CoreError, Debugger, Quirks, SymbolTable};
 use crate::{PIXEL_HEIGHT, PIXEL_WIDTH, WINDOW_HEIGHT, WINDOW_WIDTH};
 use js_sys::Math::sin;
-use macroquad::color::{Color, BLACK, RED, VIOLET};
-use macroquad::prelude::{draw_rectangle, draw_text};
+use macroquad::color::{Color, BLACK, RED, VIOLET, WHITE, YELLOW};
+use macroquad::prelude::{
+    draw_rectangle, draw_text, draw_texture_ex, vec2, DrawTextureParams, Image, Texture2D,
+};
 use std::f64::consts::PI;
-use crate::color_map::ColorMap;
+use crate::color_map::{self, ColorMap};
 
 pub fn draw_splash(last_frame_time: f64) {
     let alpha = sin(last_frame_time % PI) as f32;
@@ -17,7 +21,7 @@ pub fn draw_splash(last_frame_time: f64) {
     draw_text(str, x, y, size, Color::new(1.0, 1.0, 1.0, alpha));
 }
 
-pub fn draw_screen(screen: &Screen, color_map: &ColorMap) {
+pub fn draw_screen(screen: &Screen, color_map: &ColorMap, color_board: Option<&ColorBoard>) {
     for (ri, r) in screen.iter().enumerate() {
         for (ci, c) in r.iter().enumerate() {
             let mut color_ind: usize = 0;
@@ -27,14 +31,48 @@ pub fn draw_screen(screen: &Screen, color_map: &ColorMap) {
                 }
             }
             // let color = color_map[color_ind as usize];
-            let color = color_map.get_color(color_ind);
+            let color = match color_board {
+                // CHIP-8X colours by zone rather than by plane, the screen holds lores pixels doubled
+                Some(board) => color_map::from_rgb(match color_ind {
+                    0 => chip8x::BACKGROUND_COLORS[board.background as usize],
+                    _ => chip8x::FOREGROUND_COLORS[board.foreground_at(ci / 2, ri / 2) as usize],
+                }),
+                None => *color_map.get_color(color_ind),
+            };
             let x = ci as f32 * PIXEL_WIDTH;
             let y = ri as f32 * PIXEL_HEIGHT;
-            draw_rectangle(x, y, PIXEL_WIDTH, PIXEL_HEIGHT, *color);
+            draw_rectangle(x, y, PIXEL_WIDTH, PIXEL_HEIGHT, color);
         }
     }
 }
 
+// The MEGA-CHIP frame, scaled to the window height and centred, faded by its alpha
+pub fn draw_mega_screen(texture: &Texture2D, mega: &MegaScreen) {
+    let mut bytes = Vec::with_capacity(MEGA_COLS * MEGA_ROWS * 4);
+    for rgb in mega.frame() {
+        bytes.extend_from_slice(&[(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 0xFF]);
+    }
+    texture.update(&Image {
+        bytes,
+        width: MEGA_COLS as u16,
+        height: MEGA_ROWS as u16,
+    });
+
+    let height = WINDOW_HEIGHT as f32;
+    let width = height * MEGA_COLS as f32 / MEGA_ROWS as f32;
+    draw_rectangle(0.0, 0.0, WINDOW_WIDTH as f32, height, BLACK);
+    draw_texture_ex(
+        texture,
+        (WINDOW_WIDTH as f32 - width) / 2.0,
+        0.0,
+        Color::new(1.0, 1.0, 1.0, mega.alpha as f32 / 255.0),
+        DrawTextureParams {
+            dest_size: Some(vec2(width, height)),
+            ..Default::default()
+        },
+    );
+}
+
 pub fn draw_pause() {
     let pause_size = 48.0;
     let pause_str = "[PAUSED]";
@@ -75,6 +113,49 @@ pub fn draw_basic_debug_info(quirks: &Quirks, ticks_per_frame: u32, frame_delta:
     );
 }
 
+pub fn draw_status(msg: &str) {
+    draw_text(msg, 12.0, WINDOW_HEIGHT as f32 - 12.0, 20.0, RED);
+}
+
+pub fn draw_debugger(chip: &Chip8, debugger: &Debugger, cursor: u16) {
+    let width = 260.0;
+    let x = WINDOW_WIDTH as f32 - width;
+    let font_size = 18.0;
+    let line_height = 16.0;
+    draw_rectangle(x, 0.0, width, WINDOW_HEIGHT as f32, Color::new(0.0, 0.0, 0.0, 0.8));
+
+    // A few instructions before the cursor, the rest after
+    let start = cursor.saturating_sub(8);
+    for row in 0..12u16 {
+        let addr = start.wrapping_add(row * 2);
+        let y = 14.0 + row as f32 * line_height;
+        if addr == cursor {
+            draw_rectangle(x, y - line_height + 4.0, width, line_height, Color::new(0.3, 0.3, 0.6, 1.0));
+        }
+        let pc_mark = if addr == chip.pc() { ">" } else { " " };
+        let bp_mark = if debugger.has_breakpoint(addr) { "*" } else { " " };
+        let instruction = match disasm::decode_at(chip.memory(), addr, chip.quirks_mode().mode) {
+            Some(i) => format!("{:04X} {}", i.opcode, i.symbolic(chip.symbols())),
+            None => "----".to_string(),
+        };
+        let line = format!("{}{} {:04X}  {}", pc_mark, bp_mark, addr, instruction);
+        let color = if addr == chip.pc() { YELLOW } else { WHITE };
+        draw_text(&line, x + 6.0, y, font_size, color);
+    }
+
+    let stack = chip
+        .stack()
+        .iter()
+        .map(|&a| match chip.symbols().describe(a) {
+            Some(name) => name,
+            None => format!("{:03X}", a),
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    let footer = format!("Stack: [{}]\nF8 run  F10 over  F11 into\nS+F11 out  F12 to cursor", stack);
+    draw_string_lines(&footer, x + 6.0, 12.0 * line_height, 16.0, WHITE);
+}
+
 pub fn draw_emu_state(state_str: &str) {
     let debug_x: f32 = 12.0;
     let debug_y: f32 = 0.0;
@@ -94,7 +175,7 @@ pub fn draw_string_lines(str: &str, x: f32, y: f32, font_size: f32, text_color:
     });
 }
 
-pub fn show_error(err: &CoreError) {
+pub fn show_error(err: &CoreError, symbols: &SymbolTable) {
     let debug_x = 30.0;
     let debug_y = 70.0;
     let font_size = 24.0;
@@ -122,6 +203,38 @@ pub fn show_error(err: &CoreError) {
     );
 
     // Error info
-    let err_text = format!("Type: {}\nInfo: {}", err.error_type, err.info);
+    let mut err_text = format!("Type: {}\nInfo: {}", err.error_type, err.info);
+    if let Some(name) = err.error_type.pc().and_then(|pc| symbols.describe(pc)) {
+        err_text = format!("{}\nPC location: {}", err_text, name);
+    }
     draw_string_lines(&err_text, debug_x, debug_y, font_size, text_color);
 }
+
+pub fn draw_exited(pc: u16, symbols: &SymbolTable) {
+    let box_color = Color::from_rgba(70, 90, 120, 255);
+    let box_color2 = Color::from_rgba(50, 66, 90, 255);
+    let text_color = Color::from_rgba(255, 255, 255, 255);
+
+    draw_rectangle(
+        16.0,
+        16.0,
+        (WINDOW_WIDTH - 32) as f32,
+        (WINDOW_HEIGHT - 32) as f32,
+        box_color,
+    );
+    draw_rectangle(24.0, 24.0, (WINDOW_WIDTH - 48) as f32, 42.0, box_color2);
+    draw_text(
+        "INTERPRETER EXITED",
+        WINDOW_WIDTH as f32 / 2.0 - 130.0,
+        54.0,
+        32.0,
+        text_color,
+    );
+
+    let mut text = format!("The program ran 00FD at 0x{:04X}", pc);
+    if let Some(name) = symbols.describe(pc) {
+        text = format!("{}\nPC location: {}", text, name);
+    }
+    text = format!("{}\n\nPress Enter to restart", text);
+    draw_string_lines(&text, 30.0, 70.0, 24.0, text_color);
+}
@@ -19,21 +19,31 @@ use wasm_bindgen::JsValue;
 mod audio;
 mod color_map;
 mod config;
-mod core;
 mod display;
+mod rom_db;
+mod storage;
 mod util;
 
 use crate::color_map::ColorMap;
 use crate::config::Config;
-use crate::core::error::CoreError;
-use crate::core::quirks::Mode;
-use core::Chip8;
-use core::{DISPLAY_COLS, DISPLAY_ROWS};
+use crate::rom_db::RomDatabase;
+use chip8_core::types::Key;
+use chip8_core::asm;
+use chip8_core::font::Font;
+#[cfg(feature = "chip-audio")]
+use chip8_core::megachip::SampleEvent;
+use chip8_core::megachip::{MEGA_COLS, MEGA_ROWS};
+use chip8_core::{Chip8, CoreError, CoreErrorType, Debugger, Mode, Quirks, RewindBuffer, Rng, RngKind, StopReason, SymbolTable, Timing};
+use chip8_core::watch::{parse_breakpoint, Condition, Watchpoint};
+use chip8_core::{DISPLAY_COLS, DISPLAY_ROWS};
 
 const WINDOW_HEIGHT: i32 = 256;
 const WINDOW_WIDTH: i32 = 512;
 const PIXEL_WIDTH: f32 = WINDOW_WIDTH as f32 / DISPLAY_COLS as f32;
 const PIXEL_HEIGHT: f32 = WINDOW_HEIGHT as f32 / DISPLAY_ROWS as f32;
+const STATUS_DURATION: f64 = 2.0;
+// A snapshot of MEGA-CHIP's 16 MiB every frame would be too slow to keep up
+const REWIND_MAX_MEMORY: usize = 1 << 16;
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
@@ -71,6 +81,12 @@ fn fetch_config() -> Config {
         "2" => { conf.core_mode = "superchipmodern".to_string() }
         "3" => { conf.core_mode = "superchiplegacy".to_string() }
         "4" => { conf.core_mode = "xochip".to_string() }
+        "5" => { conf.core_mode = "cosmacvip".to_string() }
+        "6" => { conf.core_mode = "chip8x".to_string() }
+        "7" => { conf.core_mode = "megachip".to_string() }
+        "8" => { conf.core_mode = "hireschip8".to_string() }
+        "9" => { conf.core_mode = "twopage".to_string() }
+        "10" => { conf.core_mode = "superchip10".to_string() }
         _ => {
             eprintln!("Error: Invalid Core Mode: {}\n\n", args[2]);
             usage();
@@ -86,6 +102,99 @@ fn fetch_config() -> Config {
             process::exit(1);
         }
     }
+
+    let mut options = args[4..].iter();
+    while let Some(flag) = options.next() {
+        if flag == "--source" {
+            conf.source = true;
+            continue;
+        }
+        if flag == "--no-rom-db" {
+            conf.rom_db = false;
+            continue;
+        }
+        let Some(value) = options.next() else {
+            eprintln!("Error: Missing value for {}\n\n", flag);
+            usage();
+            process::exit(1);
+        };
+        match flag.as_str() {
+            "--seed" => match value.parse::<u32>() {
+                Ok(seed) => conf.rng_seed = Some(seed),
+                Err(e) => {
+                    eprintln!("Error: Invalid seed: {}\n\n", e);
+                    usage();
+                    process::exit(1);
+                }
+            },
+            "--rng" => {
+                if RngKind::from_name(value).is_none() {
+                    eprintln!("Error: Invalid RNG: {}\n\n", value);
+                    usage();
+                    process::exit(1);
+                }
+                conf.rng = value.clone();
+            }
+            "--profile" => conf.profile = Some(value.clone()),
+            "--save-profile" => conf.save_profile = Some(value.clone()),
+            "--quirk" => {
+                if let Err(e) = Quirks::new(Mode::Chip8Modern).apply(value) {
+                    eprintln!("Error: Invalid quirk: {}\n\n", e);
+                    usage();
+                    process::exit(1);
+                }
+                conf.quirks.push(value.clone());
+            }
+            "--timing" => {
+                if Timing::from_name(value).is_none() {
+                    eprintln!("Error: Invalid timing: {}\n\n", value);
+                    usage();
+                    process::exit(1);
+                }
+                conf.timing = Some(value.clone());
+            }
+            "--trace" => conf.trace_file = Some(value.clone()),
+            "--trace-range" => {
+                if let Err(e) = chip8_core::watch::parse_pc_range(value) {
+                    eprintln!("Error: Invalid trace range: {}\n\n", e);
+                    usage();
+                    process::exit(1);
+                }
+                conf.trace_range = Some(value.clone());
+            }
+            "--trace-limit" => match value.parse::<u64>() {
+                Ok(limit) => conf.trace_limit = Some(limit),
+                Err(e) => {
+                    eprintln!("Error: Invalid trace limit: {}\n\n", e);
+                    usage();
+                    process::exit(1);
+                }
+            },
+            "--rom-db" => conf.rom_db_file = Some(value.clone()),
+            "--symbols" => conf.symbols_file = Some(value.clone()),
+            // a built in font is just the font quirk
+            "--font" => match Font::from_name(value) {
+                Some(font) => conf.quirks.push(format!("font={}", font.name())),
+                None => conf.font_file = Some(value.clone()),
+            },
+            "--gdb" => match value.parse::<u16>() {
+                Ok(port) => conf.gdb_port = Some(port),
+                Err(e) => {
+                    eprintln!("Error: Invalid GDB port: {}\n\n", e);
+                    usage();
+                    process::exit(1);
+                }
+            },
+            "--break" => conf.breakpoints.push(value.clone()),
+            "--watch" => conf.watchpoints.push(value.clone()),
+            "--break-if" => conf.break_conditions.push(value.clone()),
+            _ => {
+                eprintln!("Error: Unknown option: {}\n\n", flag);
+                usage();
+                process::exit(1);
+            }
+        }
+    }
     conf
 }
 
@@ -99,7 +208,7 @@ pub fn fetch_rom_bytes() -> Vec<u8> {
     let filename = args[1].clone();
     let bytes_result = load_rom_file(filename);
     match bytes_result {
-        Ok(bytes) => { return bytes; }
+        Ok(bytes) => bytes,
         Err(e) => {
             eprintln!("Error loading ROM: {}\n\n", e);
             usage();
@@ -147,6 +256,8 @@ enum EmuState {
     Load,
     Run,
     Error,
+    // The program ran 00FD
+    Exited,
 }
 
 static STATE: Lazy<Arc<RwLock<EmuState>>> = Lazy::new(|| Arc::new(RwLock::new(EmuState::Preload)));
@@ -156,7 +267,7 @@ async fn main() {
     #[cfg(not(target_arch = "wasm32"))]
     {
         let args: Vec<String> = env::args().collect();
-        if args.len() != 4 {
+        if args.len() < 4 {
             usage();
             process::exit(1);
         }
@@ -165,6 +276,8 @@ async fn main() {
     #[cfg(feature = "chip-audio")]
     let global_square_wave = Arc::new(Mutex::new(audio::SquareWave::new()));
     #[cfg(feature = "chip-audio")]
+    let global_sample_player = Arc::new(Mutex::new(audio::SamplePlayer::new()));
+    #[cfg(feature = "chip-audio")]
     let mut audio_device: Option<Box<dyn BaseAudioOutputDevice>> = None;
     let audio_silence = Arc::new(RwLock::new(true));
 
@@ -173,6 +286,20 @@ async fn main() {
     let global_config: Arc<Mutex<Config>> = Arc::new(Mutex::new(Config::new()));
     let mut color_map = ColorMap::new();
     let mut rom: Vec<u8>;
+    let mut rom_id = String::new();
+    let mut status: Option<(String, f64)> = None;
+    let mut rewind_buffer = RewindBuffer::new(0, 0);
+    let mut debugger = Debugger::new();
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut gdb_server: Option<chip8_core::gdb::GdbServer> = None;
+    let mut debug_cursor: u16 = 0x200;
+    // MEGA-CHIP frames are uploaded here rather than drawn pixel by pixel
+    let mega_texture = Texture2D::from_rgba8(
+        MEGA_COLS as u16,
+        MEGA_ROWS as u16,
+        &vec![0u8; MEGA_COLS * MEGA_ROWS * 4],
+    );
+    mega_texture.set_filter(FilterMode::Nearest);
 
     #[cfg(not(target_arch = "wasm32"))]
     {
@@ -181,27 +308,55 @@ async fn main() {
         drop(s);
     }
 
-    let key_map: &[(Vec<KeyCode>, core::types::Key)] = &[
-        (vec![KeyCode::Key1], core::types::Key::Key1),
-        (vec![KeyCode::Key2], core::types::Key::Key2),
-        (vec![KeyCode::Key3], core::types::Key::Key3),
-        (vec![KeyCode::Key4], core::types::Key::C),
-        (vec![KeyCode::Q], core::types::Key::Key4),
-        (vec![KeyCode::W], core::types::Key::Key5),
-        (vec![KeyCode::E, KeyCode::Space], core::types::Key::Key6),
-        (vec![KeyCode::R], core::types::Key::D),
-        (vec![KeyCode::A], core::types::Key::Key7),
-        (vec![KeyCode::S], core::types::Key::Key8),
-        (vec![KeyCode::D], core::types::Key::Key9),
-        (vec![KeyCode::F], core::types::Key::E),
-        (vec![KeyCode::Z], core::types::Key::A),
-        (vec![KeyCode::X], core::types::Key::Key0),
-        (vec![KeyCode::C], core::types::Key::B),
-        (vec![KeyCode::V], core::types::Key::F),
+    let key_map: &[(Vec<KeyCode>, Key)] = &[
+        (vec![KeyCode::Key1], Key::Key1),
+        (vec![KeyCode::Key2], Key::Key2),
+        (vec![KeyCode::Key3], Key::Key3),
+        (vec![KeyCode::Key4], Key::C),
+        (vec![KeyCode::Q], Key::Key4),
+        (vec![KeyCode::W], Key::Key5),
+        (vec![KeyCode::E, KeyCode::Space], Key::Key6),
+        (vec![KeyCode::R], Key::D),
+        (vec![KeyCode::A], Key::Key7),
+        (vec![KeyCode::S], Key::Key8),
+        (vec![KeyCode::D], Key::Key9),
+        (vec![KeyCode::F], Key::E),
+        (vec![KeyCode::Z], Key::A),
+        (vec![KeyCode::X], Key::Key0),
+        (vec![KeyCode::C], Key::B),
+        (vec![KeyCode::V], Key::F),
+    ];
+    // CHIP-8X second keypad, same layout on the numpad
+    let key2_map: &[(KeyCode, Key)] = &[
+        (KeyCode::Kp7, Key::Key1),
+        (KeyCode::Kp8, Key::Key2),
+        (KeyCode::Kp9, Key::Key3),
+        (KeyCode::KpDivide, Key::C),
+        (KeyCode::Kp4, Key::Key4),
+        (KeyCode::Kp5, Key::Key5),
+        (KeyCode::Kp6, Key::Key6),
+        (KeyCode::KpMultiply, Key::D),
+        (KeyCode::Kp1, Key::Key7),
+        (KeyCode::Kp2, Key::Key8),
+        (KeyCode::Kp3, Key::Key9),
+        (KeyCode::KpSubtract, Key::E),
+        (KeyCode::Kp0, Key::A),
+        (KeyCode::KpDecimal, Key::Key0),
+        (KeyCode::KpEnter, Key::B),
+        (KeyCode::KpAdd, Key::F),
     ];
 
+    // Closing the window goes through the loop so the trace file gets written out
+    #[cfg(not(target_arch = "wasm32"))]
+    prevent_quit();
+
     let mut last_frame_time = get_time();
     loop {
+        #[cfg(not(target_arch = "wasm32"))]
+        if is_quit_requested() {
+            flush_trace(&mut chip);
+            break;
+        }
         let config_handle = Arc::clone(&global_config);
 
         // Handle user input
@@ -215,19 +370,30 @@ async fn main() {
             }
             chip.set_key_state(*v, pressed);
         }
+        for (k, v) in key2_map.iter() {
+            chip.set_key2_state(*v, keys_pressed.contains(k));
+        }
 
-        // Switch modes
+        // Switch modes. CHIP-8X, MEGA-CHIP and the 64x64 modes load ROMs differently, so
+        // they're only picked at startup
+        let shift_down = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        if is_key_pressed(KeyCode::Key6) {
+            chip.set_quirks_mode(Quirks::new(Mode::CosmacVip));
+        }
         if is_key_pressed(KeyCode::Key7) {
-            chip.set_quirks_mode(core::quirks::Quirks::new(Mode::Chip8Modern));
+            chip.set_quirks_mode(Quirks::new(Mode::Chip8Modern));
         }
         if is_key_pressed(KeyCode::Key8) {
-            chip.set_quirks_mode(core::quirks::Quirks::new(Mode::SuperChipModern));
+            chip.set_quirks_mode(Quirks::new(Mode::SuperChipModern));
         }
         if is_key_pressed(KeyCode::Key9) {
-            chip.set_quirks_mode(core::quirks::Quirks::new(Mode::SuperChipLegacy));
+            chip.set_quirks_mode(Quirks::new(match shift_down {
+                true => Mode::SuperChip10,
+                false => Mode::SuperChipLegacy,
+            }));
         }
         if is_key_pressed(KeyCode::Key0) {
-            chip.set_quirks_mode(core::quirks::Quirks::new(Mode::XoChip));
+            chip.set_quirks_mode(Quirks::new(Mode::XoChip));
         }
 
         if is_key_pressed(KeyCode::Minus) {
@@ -256,20 +422,83 @@ async fn main() {
             config.pause_emulation = !config.pause_emulation;
         }
 
-        // TODO: Remove this
-        // BLOW UP THE CORE - just for fun
-        if is_key_pressed(KeyCode::F5) {
-            chip.chaos();
+        // Save states: F1-F4 load a slot, Shift+F1-F4 save it
+        let save_slot_keys = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4];
+        for (slot, key) in save_slot_keys.iter().enumerate() {
+            if !is_key_pressed(*key) {
+                continue;
+            }
+            let slot = slot as u8 + 1;
+            let key = storage::state_key(&rom_id, slot);
+            let msg = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                match storage::write(&key, &chip.save_state()) {
+                    Ok(_) => format!("Saved slot {}", slot),
+                    Err(e) => format!("Save failed: {}", e),
+                }
+            } else {
+                match storage::read(&key) {
+                    Some(data) => match chip.load_state(&data) {
+                        Ok(_) => format!("Loaded slot {}", slot),
+                        Err(e) => format!("Load failed: {}", e.error_type),
+                    },
+                    None => format!("Slot {} is empty", slot),
+                }
+            };
+            status = Some((msg, get_time() + STATUS_DURATION));
+        }
+
+        // Debugger controls
+        if is_key_pressed(KeyCode::F8) {
+            if debugger.is_paused() {
+                debugger.resume();
+            } else {
+                debugger.pause();
+                debug_cursor = chip.pc();
+            }
+        }
+        if is_key_pressed(KeyCode::F7) {
+            debugger.break_on_error = !debugger.break_on_error;
+            let msg = format!("Break on error: {}", debugger.break_on_error);
+            status = Some((msg, get_time() + STATUS_DURATION));
+        }
+        if is_key_pressed(KeyCode::F9) {
+            let addr = if debugger.is_paused() { debug_cursor } else { chip.pc() };
+            debugger.toggle_breakpoint(addr);
+        }
+        if debugger.is_paused() {
+            if is_key_pressed(KeyCode::F10) {
+                debugger.step_over(&chip);
+            }
+            if is_key_pressed(KeyCode::F11) {
+                if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                    debugger.step_out(&chip);
+                } else {
+                    debugger.step_into();
+                }
+            }
+            if is_key_pressed(KeyCode::F12) {
+                debugger.run_to(debug_cursor);
+            }
+            if is_key_pressed(KeyCode::Up) {
+                debug_cursor = debug_cursor.saturating_sub(2);
+            }
+            if is_key_pressed(KeyCode::Down) {
+                debug_cursor = debug_cursor.saturating_add(2);
+            }
         }
 
         // Draw the screen
         chip.v_blank();
-        display::draw_screen(&(chip.get_screen().lock().unwrap()), &color_map);
+        match chip.mega_screen() {
+            Some(mega) => display::draw_mega_screen(&mega_texture, mega),
+            None => display::draw_screen(&(chip.get_screen().lock().unwrap()), &color_map, chip.color_board()),
+        }
 
         let current_state = {
             let state_read = STATE.read().unwrap();
             *state_read
         };
+        let mut rewinding = false;
         match current_state {
             EmuState::Preload => {
                 display::draw_splash(last_frame_time);
@@ -277,53 +506,225 @@ async fn main() {
             EmuState::Load => {
                 #[cfg(feature = "chip-audio")]
                 if audio_device.is_none() {
-                    audio_device = audio::init_audio(&global_square_wave, &global_config, &audio_silence);
+                    audio_device = audio::init_audio(
+                        &global_square_wave,
+                        &global_sample_player,
+                        &global_config,
+                        &audio_silence,
+                    );
                 }
 
                 chip.reset();
+                #[cfg(feature = "chip-audio")]
+                global_sample_player.lock().unwrap().stop();
                 rom = fetch_rom_bytes();
                 let new_config = fetch_config();
                 let mut config_handle = global_config.lock().unwrap();
                 config_handle.update(new_config);
-                chip.set_core_mode(&config_handle.core_mode);
+                let mut load_error = None;
+                let mut symbols = SymbolTable::new();
+                if config_handle.source {
+                    match asm::assemble(&String::from_utf8_lossy(&rom)) {
+                        Ok(program) => {
+                            symbols = SymbolTable::from_labels(&program.labels);
+                            rom = program.bytes;
+                        }
+                        Err(e) => {
+                            rom = vec![];
+                            load_error = Some(CoreError::new(
+                                "Assembling source".to_string(),
+                                CoreErrorType::InvalidRom(e.to_string()),
+                            ));
+                        }
+                    }
+                }
+                rom_id = storage::rom_id(&rom);
+                if let Err(e) = chip.set_core_mode(&config_handle.core_mode) {
+                    load_error = load_error.or(Some(e));
+                }
+                let mut preset = chip.quirks_mode().clone();
+                if config_handle.rom_db {
+                    match load_rom_db(&config_handle) {
+                        Ok(db) => {
+                            if let Some(info) = db.lookup(&rom_id) {
+                                println!("ROM database: {}", info);
+                                if let Some(tickrate) = info.tickrate {
+                                    config_handle.ticks_per_frame = tickrate;
+                                }
+                                for (slot, color) in config_handle.color_map.iter_mut().zip(info.colors.iter()) {
+                                    *slot = *color;
+                                }
+                                status = Some((info.to_string(), get_time() + STATUS_DURATION));
+                                preset = info.quirks;
+                            }
+                        }
+                        Err(e) => status = Some((e, get_time() + STATUS_DURATION)),
+                    }
+                }
+                match setup_quirks(preset, &config_handle) {
+                    Ok(quirks) => chip.set_quirks_mode(quirks),
+                    Err(e) => status = Some((e, get_time() + STATUS_DURATION)),
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Err(e) = load_font(&mut chip, &config_handle) {
+                    status = Some((e, get_time() + STATUS_DURATION));
+                }
 
                 color_map.set_int_color_map(&config_handle.color_map);
 
-                let loaded = chip.load_rom(rom, 0x200);
+                let rng_kind = RngKind::from_name(&config_handle.rng).unwrap_or(RngKind::Xorshift);
+                let rng_seed = config_handle
+                    .rng_seed
+                    .unwrap_or((miniquad::date::now() * 1000.0) as u32);
+                chip.set_rng(Rng::new(rng_kind, rng_seed));
+                // RPL flags (Fx75/Fx85) survive restarts like on the HP-48
+                if let Some(flags) = storage::read(&storage::rpl_key(&rom_id)) {
+                    chip.set_rpl_flags(&flags);
+                }
+                debugger.resume();
+                debugger.break_on_error = config_handle.break_on_error;
+                if let Err(e) = setup_debugger(&mut debugger, &config_handle) {
+                    status = Some((e, get_time() + STATUS_DURATION));
+                }
+                // A symbol file replaces the labels of assembled source
+                #[cfg(not(target_arch = "wasm32"))]
+                match load_symbols(&config_handle) {
+                    Ok(Some(loaded)) => symbols = loaded,
+                    Ok(None) => {}
+                    Err(e) => status = Some((e, get_time() + STATUS_DURATION)),
+                }
+                chip.set_symbols(symbols);
+                #[cfg(not(target_arch = "wasm32"))]
+                match setup_tracer(&config_handle) {
+                    Ok(tracer) => chip.set_tracer(tracer),
+                    Err(e) => status = Some((e, get_time() + STATUS_DURATION)),
+                }
+                // The server outlives reloads so an attached gdb stays connected
+                #[cfg(not(target_arch = "wasm32"))]
+                if let (None, Some(port)) = (&gdb_server, config_handle.gdb_port) {
+                    match chip8_core::gdb::GdbServer::bind(&format!("127.0.0.1:{}", port)) {
+                        Ok(server) => {
+                            println!("GDB server listening on 127.0.0.1:{}", port);
+                            gdb_server = Some(server);
+                        }
+                        Err(e) => status = Some((format!("GDB server: {}", e), get_time() + STATUS_DURATION)),
+                    }
+                }
+                rewind_buffer = RewindBuffer::new(
+                    config_handle.rewind_seconds as usize * 60,
+                    config_handle.rewind_budget_kb as usize * 1024,
+                );
+
+                // assembled source is always built for asm::PROGRAM_START
+                let start = match config_handle.source {
+                    true => asm::PROGRAM_START,
+                    false => chip.quirks_mode().mode.program_start(),
+                };
+                let loaded = chip.load_rom(rom, start);
                 match loaded {
                     Ok(b) => {
                         println!("Loaded {:?} ROM bytes", b);
+                        if chip.memory().len() > REWIND_MAX_MEMORY && config_handle.rewind_seconds > 0 {
+                            status = Some((
+                                format!("Rewind is unavailable in {} mode", chip.quirks_mode().mode_label),
+                                get_time() + STATUS_DURATION,
+                            ));
+                        }
                     }
                     Err(err) => {
-                        panic!("Error loading ROM bytes: {}", err);
+                        println!("Error loading ROM bytes: {}", err);
+                        load_error = load_error.or(Some(err));
                     }
                 }
 
                 let mut state_writer = STATE.write().unwrap();
-                *state_writer = EmuState::Run;
+                *state_writer = match load_error {
+                    Some(e) => {
+                        core_error = Some(e);
+                        EmuState::Error
+                    }
+                    None => EmuState::Run,
+                };
                 drop(config_handle);
             }
             EmuState::Run => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(server) = gdb_server.as_mut() {
+                    if let Err(e) = server.poll(&mut chip, &mut debugger) {
+                        status = Some((format!("GDB: {}", e), get_time() + STATUS_DURATION));
+                    }
+                }
                 // Run processor
                 let config_handle = Arc::clone(&global_config);
                 let config = config_handle.lock().unwrap();
 
-                if !config.pause_emulation {
-                    for _ in 0..config.ticks_per_frame {
-                        if let Err(e) = chip.step() {
+                // Rewind: hold Backspace to run backwards, or tap it while paused to step back a frame
+                rewinding = if config.pause_emulation {
+                    is_key_pressed(KeyCode::Backspace)
+                } else {
+                    is_key_down(KeyCode::Backspace)
+                };
+
+                if rewinding {
+                    if let Some(state) = rewind_buffer.rewind() {
+                        if let Err(e) = chip.load_state(&state) {
                             println!("Error: {:#?}", e);
+                        }
+                    }
+                    *audio_silence.write().unwrap() = true;
+                } else if !config.pause_emulation && !debugger.is_paused() {
+                    let budget = chip.quirks_mode().timing.frame_budget(config.ticks_per_frame);
+                    match debugger.run(&mut chip, budget) {
+                        Ok(Some(StopReason::Exited(pc))) => {
+                            println!("Interpreter exited at 0x{:04X}", pc);
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if let Some(server) = gdb_server.as_mut() {
+                                let _ = server.report_exit();
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            flush_trace(&mut chip);
+                            let mut state_writer = STATE.write().unwrap();
+                            *state_writer = EmuState::Exited;
+                        }
+                        Ok(Some(reason)) => {
+                            debug_cursor = chip.pc();
+                            status = Some((reason.to_string(), get_time() + STATUS_DURATION));
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            println!("Error: {:#?}", e);
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if let Some(server) = gdb_server.as_mut() {
+                                let _ = server.report_error();
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            flush_trace(&mut chip);
                             core_error = Some(e);
                             let mut state_writer = STATE.write().unwrap();
                             *state_writer = EmuState::Error;
-                            break;
                         }
                     }
+                    if chip.take_rpl_changed() {
+                        if let Err(e) = storage::write(&storage::rpl_key(&rom_id), chip.rpl_flags()) {
+                            status = Some((format!("Saving RPL flags failed: {}", e), get_time() + STATUS_DURATION));
+                        }
+                    }
+                    for warning in chip.take_warnings() {
+                        println!("Warning: {}", warning.error_type);
+                        let msg = warning.error_type.to_string().replace('\n', ", ");
+                        status = Some((msg, get_time() + STATUS_DURATION));
+                    }
+
+                    // Timers only advance on frames that ran to completion
+                    let frame_done = !debugger.is_paused();
 
                     #[cfg(not(feature = "chip-audio"))]
-                    chip.tick_timers();
+                    if frame_done {
+                        chip.tick_timers();
+                    }
 
                     #[cfg(feature = "chip-audio")]
-                    if audio_device.is_some() {
+                    if frame_done && audio_device.is_some() {
                         let (st, _) = chip.tick_timers();
                         let sw_handle = Arc::clone(&global_square_wave);
                         if st > 0 {
@@ -331,7 +732,7 @@ async fn main() {
                                 let mut silence_writer = audio_silence.write().unwrap();
                                 *silence_writer = false;
                             }
-                            if let Mode::XoChip = chip.quirks_mode().mode {
+                            if let Mode::XoChip | Mode::Chip8X = chip.quirks_mode().mode {
                                 if let Some(snd) = chip.get_sound() {
                                     sw_handle
                                         .lock()
@@ -352,18 +753,39 @@ async fn main() {
                             *silence_writer = true;
                         }
                     }
+
+                    #[cfg(feature = "chip-audio")]
+                    match chip.take_sample_event() {
+                        Some(SampleEvent::Play(sample)) => global_sample_player.lock().unwrap().play(sample),
+                        Some(SampleEvent::Stop) => global_sample_player.lock().unwrap().stop(),
+                        None => {}
+                    }
+
+                    if chip.memory().len() <= REWIND_MAX_MEMORY {
+                        rewind_buffer.push(chip.save_state());
+                    }
+                } else if debugger.is_paused() {
+                    *audio_silence.write().unwrap() = true;
                 }
             }
             EmuState::Error => {
                 if let Some(err) = &core_error {
-                    display::show_error(err);
+                    display::show_error(err, chip.symbols());
                 }
-                if is_key_pressed(KeyCode::Enter) {
+                if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Backspace) {
                     let mut state_writer = STATE.write().unwrap();
                     *state_writer = EmuState::Run;
                     core_error = None;
                 }
             }
+            EmuState::Exited => {
+                display::draw_exited(chip.pc(), chip.symbols());
+                if is_key_pressed(KeyCode::Enter) {
+                    // Reload the ROM from scratch
+                    let mut state_writer = STATE.write().unwrap();
+                    *state_writer = EmuState::Load;
+                }
+            }
         };
 
         let now = get_time();
@@ -374,6 +796,14 @@ async fn main() {
                 display::draw_pause();
             }
 
+            if rewinding {
+                display::draw_status(&format!("<< Rewind ({} frames)", rewind_buffer.len()));
+            }
+
+            if debugger.is_paused() {
+                display::draw_debugger(&chip, &debugger, debug_cursor);
+            }
+
             if config.debug_draw > 0 {
                 display::draw_basic_debug_info(
                     chip.quirks_mode(),
@@ -387,21 +817,187 @@ async fn main() {
             }
         }
 
+        if let Some((msg, expires)) = &status {
+            if now < *expires {
+                display::draw_status(msg);
+            } else {
+                status = None;
+            }
+        }
+
         last_frame_time = now;
         next_frame().await;
     }
 }
 
+// The configured ROM database file, or the built-in one
+fn load_rom_db(config: &Config) -> Result<RomDatabase, String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(file) = &config.rom_db_file {
+        let json = std::fs::read_to_string(file).map_err(|e| format!("ROM database {}: {}", file, e))?;
+        return RomDatabase::parse(&json).map_err(|e| format!("ROM database {}: {}", file, e));
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = config;
+    RomDatabase::built_in().map_err(|e| format!("ROM database: {}", e))
+}
+
+/*
+   The quirks to run with: the configured profile (or the mode's preset), then the timing
+   and individual quirk overrides. Saves the result when save_profile is set
+*/
+fn setup_quirks(preset: Quirks, config: &Config) -> Result<Quirks, String> {
+    let mut quirks = match &config.profile {
+        Some(name) => {
+            let text = read_profile(name).ok_or(format!("Profile {}: not found", name))?;
+            Quirks::parse_profile(&text).map_err(|e| format!("Profile {}: {}", name, e))?
+        }
+        None => preset,
+    };
+    if let Some(timing) = config.timing.as_deref().and_then(Timing::from_name) {
+        quirks.timing = timing;
+    }
+    for spec in config.quirks.iter() {
+        quirks.apply(spec).map_err(|e| format!("Quirk {}: {}", spec, e))?;
+    }
+    if let Some(name) = &config.save_profile {
+        if !storage::is_valid_name(name) {
+            return Err(format!("Profile name {}: use letters, digits, '-' and '_'", name));
+        }
+        quirks.mode_label = name.clone();
+        storage::write(&storage::profile_key(name), quirks.to_profile().as_bytes())
+            .map_err(|e| format!("Saving profile {}: {}", name, e))?;
+    }
+    Ok(quirks)
+}
+
+// A saved profile by name, or on desktop a profile file
+fn read_profile(name: &str) -> Option<String> {
+    let saved = if storage::is_valid_name(name) {
+        storage::read(&storage::profile_key(name))
+    } else {
+        None
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let saved = saved.or_else(|| std::fs::read(name).ok());
+    saved.map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+}
+
+// Replaces the debugger's breakpoints, watchpoints and conditions with the configured ones
+fn setup_debugger(debugger: &mut Debugger, config: &Config) -> Result<(), String> {
+    debugger.clear_breakpoints();
+    debugger.clear_watchpoints();
+    debugger.clear_conditions();
+    for spec in config.breakpoints.iter() {
+        let (addr, condition) =
+            parse_breakpoint(spec).map_err(|e| format!("Breakpoint {}: {}", spec, e))?;
+        debugger.set_breakpoint(addr, condition);
+    }
+    for spec in config.watchpoints.iter() {
+        let watchpoint =
+            Watchpoint::parse(spec).map_err(|e| format!("Watchpoint {}: {}", spec, e))?;
+        debugger.add_watchpoint(watchpoint);
+    }
+    for spec in config.break_conditions.iter() {
+        let condition =
+            Condition::parse(spec).map_err(|e| format!("Condition {}: {}", spec, e))?;
+        debugger.add_condition(condition);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn setup_tracer(config: &Config) -> Result<Option<chip8_core::Tracer>, String> {
+    use chip8_core::watch::parse_pc_range;
+    use chip8_core::Tracer;
+
+    let Some(trace_file) = &config.trace_file else {
+        return Ok(None);
+    };
+    let file = std::fs::File::create(trace_file)
+        .map_err(|e| format!("Trace {}: {}", trace_file, e))?;
+    let mut tracer = Tracer::to_writer(Box::new(std::io::BufWriter::new(file)));
+    if let Some(range) = &config.trace_range {
+        tracer.set_range(Some(parse_pc_range(range).map_err(|e| format!("Trace range: {}", e))?));
+    }
+    tracer.set_limit(config.trace_limit);
+    Ok(Some(tracer))
+}
+
+// The trace file is buffered, write out what's pending once nothing more will run
+#[cfg(not(target_arch = "wasm32"))]
+fn flush_trace(chip: &mut Chip8) {
+    let Some(tracer) = chip.tracer_mut() else {
+        return;
+    };
+    let result = match tracer.error() {
+        Some(e) => Err(e.to_string()),
+        None => tracer.flush().map_err(|e| e.to_string()),
+    };
+    if let Err(e) = result {
+        println!("Error writing trace: {}", e);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_font(chip: &mut Chip8, config: &Config) -> Result<(), String> {
+    let Some(font_file) = &config.font_file else {
+        return Ok(());
+    };
+    let bytes = std::fs::read(font_file).map_err(|e| format!("Font {}: {}", font_file, e))?;
+    chip.set_custom_font(Some(&bytes))
+        .map_err(|e| format!("Font {}: {}", font_file, e.error_type))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_symbols(config: &Config) -> Result<Option<SymbolTable>, String> {
+    let Some(symbols_file) = &config.symbols_file else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(symbols_file)
+        .map_err(|e| format!("Symbols {}: {}", symbols_file, e))?;
+    SymbolTable::parse(&text)
+        .map(Some)
+        .map_err(|e| format!("Symbols {}: {}", symbols_file, e))
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn usage() {
     let args: Vec<String> = env::args().collect();
-    eprintln!("Usage: {} <Filename> <CHIP Mode> <Ticks-per-frame>", args[0]);
+    eprintln!(
+        "Usage: {} <Filename> <CHIP Mode> <Ticks-per-frame> [--seed <N>] [--rng <xorshift|vip>] [--timing <instructions|vip>] [--profile <Name|File>] [--quirk <Name>=<Value>] [--save-profile <Name>] [--rom-db <File>] [--no-rom-db] [--break <Spec>] [--watch <Spec>] [--break-if <Condition>] [--source] [--trace <File>] [--trace-range <Start>-<End>] [--trace-limit <N>] [--symbols <File>] [--font <Name|File>] [--gdb <Port>]",
+        args[0]
+    );
     eprintln!();
     eprintln!("<Filename> - path to ROM File");
     eprintln!("<CHIP Mode>");
     eprintln!("\t1 - CHIP-8");
     eprintln!("\t2 - SuperChip Modern");
-    eprintln!("\t3 - SuperChip Legacy");
+    eprintln!("\t3 - SuperChip Legacy (SCHIP 1.1)");
     eprintln!("\t4 - XO-Chip");
-    eprintln!("<Ticks-per-frame> - Number of instructions emulated per frame\n");
+    eprintln!("\t5 - COSMAC VIP (original timing, <Ticks-per-frame> is ignored)");
+    eprintln!("\t6 - CHIP-8X (VIP timing, second keypad on the numpad)");
+    eprintln!("\t7 - MEGA-CHIP");
+    eprintln!("\t8 - HIRES CHIP-8 (VIP timing, 64x64 after 1260 at 0x200)");
+    eprintln!("\t9 - CHIP-8 two-page display (VIP timing, 64x64)");
+    eprintln!("\t10 - SuperChip 1.0");
+    eprintln!("<Ticks-per-frame> - Number of instructions emulated per frame");
+    eprintln!("--seed <N> - seed for the Cxkk random number generator (default: clock)");
+    eprintln!("--rng <xorshift|vip> - Cxkk random number generator");
+    eprintln!("--timing <instructions|vip> - run <Ticks-per-frame> instructions or COSMAC VIP machine cycles per frame (default: per mode)");
+    eprintln!("--profile <Name|File> - quirk profile saved under <Name> or read from <File>, instead of the <CHIP Mode> preset");
+    eprintln!("--quirk <Name>=<Value> - set one quirk, eg. 'vf_reset=false' (repeatable)");
+    eprintln!("--save-profile <Name> - save the resulting quirks as a named profile");
+    eprintln!("--rom-db <File> - chip-8-database programs.json to identify ROMs with (default: built in)");
+    eprintln!("--no-rom-db - always use <CHIP Mode> and <Ticks-per-frame>, even for ROMs in the database");
+    eprintln!("--break <Addr>[ if <Condition>] - breakpoint, eg. '0x2A4 if V3 == 0x10' (repeatable)");
+    eprintln!("--watch <r|w|rw>:<Start>[-<End>] - break on memory access, eg. 'w:0x300-0x31F' (repeatable)");
+    eprintln!("--break-if <Condition> - break when a condition becomes true, eg. 'I >= 0x1000' (repeatable)");
+    eprintln!("--source - <Filename> is Octo (.8o) source, assemble it before running");
+    eprintln!("--trace <File> - write every executed instruction to a file");
+    eprintln!("--trace-range <Start>-<End> - only trace instructions in this address range");
+    eprintln!("--trace-limit <N> - stop tracing after N instructions");
+    eprintln!("--symbols <File> - symbol map ('name = 0x2A4' lines) for the debugger, traces and errors");
+    eprintln!("--font <Name|File> - vip, dream6800, eti660, fishnchips, schip, octo or a font file (default: per mode)");
+    eprintln!("--gdb <Port> - accept a GDB remote protocol connection on 127.0.0.1:<Port>\n");
 }
@@ -0,0 +1,166 @@
+use chip8_core::font::Font;
+use chip8_core::{Mode, Quirks};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/*
+   ROM metadata database in the format of the community chip-8-database (programs.json).
+
+   It is a list of programs, each with its ROMs keyed by SHA-1. A ROM lists the platforms
+   it runs on (best first) and optionally quirk overrides per platform, a tickrate in
+   instructions per frame, display colors and a font style. On load the frontend looks
+   the ROM up and uses what it finds in place of the CLI mode, ticks per frame, color map
+   and font.
+
+   roms/database.json covers the ROMs in roms/ and is built in. A full copy of the
+   community database can be loaded from a file instead.
+*/
+
+const BUILT_IN: &str = include_str!("../roms/database.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    font_style: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// What the database says about one ROM, resolved for this emulator
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: String,
+    pub quirks: Quirks,
+    pub tickrate: Option<u32>,
+    // background first, then one per plane combination
+    pub colors: Vec<u32>,
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.title)?;
+        if !self.authors.is_empty() {
+            write!(f, " by {}", self.authors.join(", "))?;
+        }
+        write!(f, " ({})", self.platform)
+    }
+}
+
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    // SHA-1 -> index into programs
+    index: HashMap<String, usize>,
+}
+
+impl RomDatabase {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let programs: Vec<Program> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut index = HashMap::new();
+        for (i, program) in programs.iter().enumerate() {
+            for hash in program.roms.keys() {
+                index.insert(hash.to_lowercase(), i);
+            }
+        }
+        Ok(Self { programs, index })
+    }
+
+    pub fn built_in() -> Result<Self, String> {
+        Self::parse(BUILT_IN)
+    }
+
+    // The ROM with this SHA-1, if it runs on a platform this emulator has
+    pub fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let sha1 = sha1.to_lowercase();
+        let program = &self.programs[*self.index.get(&sha1)?];
+        let rom = program
+            .roms
+            .iter()
+            .find(|(hash, _)| hash.to_lowercase() == sha1)
+            .map(|(_, rom)| rom)?;
+        let (platform, mode) = rom
+            .platforms
+            .iter()
+            .find_map(|p| platform_mode(p).map(|mode| (p, mode)))?;
+
+        let mut quirks = Quirks::new(mode);
+        if let Some(overrides) = rom.quirky_platforms.get(platform) {
+            for (name, value) in overrides.iter() {
+                apply_quirk(&mut quirks, name, *value);
+            }
+        }
+        if let Some(font) = rom.font_style.as_deref().and_then(Font::from_name) {
+            quirks.font = font;
+        }
+        let colors = match &rom.colors {
+            Some(colors) => colors.pixels.iter().filter_map(|c| parse_color(c)).collect(),
+            None => vec![],
+        };
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platform: platform.clone(),
+            quirks,
+            tickrate: rom.tickrate,
+            colors,
+        })
+    }
+}
+
+// Platform ids of the database onto our modes
+fn platform_mode(platform: &str) -> Option<Mode> {
+    match platform {
+        "originalChip8" | "hybridVIP" => Some(Mode::CosmacVip),
+        "modernChip8" => Some(Mode::Chip8Modern),
+        "chip48" | "superchip1" => Some(Mode::SuperChip10),
+        "superchip" => Some(Mode::SuperChipLegacy),
+        "xochip" => Some(Mode::XoChip),
+        "chip8x" => Some(Mode::Chip8X),
+        "megachip8" => Some(Mode::MegaChip),
+        _ => None,
+    }
+}
+
+// Quirk names of the database (quirks.json) onto ours, unknown ones are ignored
+fn apply_quirk(quirks: &mut Quirks, name: &str, value: bool) {
+    match name {
+        "shift" => quirks.shifting_vx = value,
+        "memoryLeaveIUnchanged" => quirks.load_store_index_increase = !value,
+        // I ends at I + X instead of I + X + 1, close enough to an increase
+        "memoryIncrementByX" if value => quirks.load_store_index_increase = true,
+        "wrap" => quirks.clipping = !value,
+        "jump" => quirks.jump_plus_vx = value,
+        "vblank" => quirks.display_wait = value,
+        "logic" => quirks.vf_reset = value,
+        _ => {}
+    }
+}
+
+// "#RRGGBB" as 0xRRGGBB
+fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
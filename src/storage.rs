@@ -0,0 +1,67 @@
+use sha1_smol::Sha1;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::Path};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+// Persistent data is keyed by the SHA-1 of the ROM so it follows the ROM, not its filename
+pub fn rom_id(rom: &[u8]) -> String {
+    Sha1::from(rom).digest().to_string()
+}
+
+pub fn state_key(rom_id: &str, slot: u8) -> String {
+    format!("{}.state{}", rom_id, slot)
+}
+
+pub fn rpl_key(rom_id: &str) -> String {
+    format!("{}.rpl", rom_id)
+}
+
+pub fn profile_key(name: &str) -> String {
+    format!("{}.profile", name)
+}
+
+// Names that are safe to use in a key (and file name)
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_DIR: &str = "saves";
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(key: &str, data: &[u8]) -> Result<(), String> {
+    fs::create_dir_all(SAVE_DIR).map_err(|e| e.to_string())?;
+    fs::write(Path::new(SAVE_DIR).join(key), data).map_err(|e| e.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(key: &str) -> Option<Vec<u8>> {
+    fs::read(Path::new(SAVE_DIR).join(key)).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = window)]
+    fn storage_write(key: &str, data: &[u8]);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn storage_read(key: &str) -> Option<js_sys::Uint8Array>;
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(key: &str, data: &[u8]) -> Result<(), String> {
+    storage_write(key, data);
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(key: &str) -> Option<Vec<u8>> {
+    storage_read(key).map(|a| a.to_vec())
+}